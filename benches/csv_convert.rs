@@ -0,0 +1,77 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use rcli::{
+    process_csv, ConvertOptions, DialectOptions, InferMode, InputFormat, OutputFormat,
+    WriterOptions,
+};
+use std::{fs::File, io::BufWriter, io::Write, path::PathBuf};
+
+const ROWS: usize = 500_000;
+const POSITIONS: &[&str] = &["Goalkeeper", "Centre-Back", "Left-Back", "Central Midfield"];
+
+/// A players file like assets/juventus.csv, generated once and reused between runs.
+fn fixture() -> PathBuf {
+    let path = std::env::temp_dir().join(format!("rcli-bench-players-{}.csv", ROWS));
+    if path.exists() {
+        return path;
+    }
+    let mut file = BufWriter::new(File::create(&path).unwrap());
+    writeln!(file, "Name,Position,DOB,Nationality,Kit Number,Rating").unwrap();
+    for i in 0..ROWS {
+        writeln!(
+            file,
+            "Player {},{},\"Jan {}, 1990 (29)\",Italy,{},{}.{}",
+            i,
+            POSITIONS[i % POSITIONS.len()],
+            i % 28 + 1,
+            i % 99 + 1,
+            i % 10,
+            i % 100
+        )
+        .unwrap();
+    }
+    file.flush().unwrap();
+    path
+}
+
+fn options(jobs: usize) -> ConvertOptions {
+    ConvertOptions {
+        input_format: InputFormat::Csv,
+        format: OutputFormat::Json,
+        dialect: DialectOptions::default(),
+        infer: InferMode::Cell,
+        types: vec![],
+        filter: None,
+        select: vec![],
+        rename: vec![],
+        arrays: false,
+        unflatten: false,
+        flatten: false,
+        skip_bad_rows: false,
+        rejects: None,
+        union: false,
+        source_column: None,
+        jobs,
+        writer: WriterOptions::default(),
+    }
+}
+
+fn bench_csv_to_json(c: &mut Criterion) {
+    let inputs = [fixture().display().to_string()];
+    let output = std::env::temp_dir()
+        .join("rcli-bench-players.json")
+        .display()
+        .to_string();
+    let mut group = c.benchmark_group("csv_to_json");
+    group.sample_size(10);
+    // jobs 0 is one thread per core
+    for jobs in [1, 0] {
+        group.bench_with_input(BenchmarkId::new("jobs", jobs), &jobs, |b, &jobs| {
+            let opts = options(jobs);
+            b.iter(|| process_csv(&inputs, &output, &opts).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_csv_to_json);
+criterion_main!(benches);
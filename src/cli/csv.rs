@@ -1,50 +1,716 @@
 use super::verify_file;
-use crate::process_csv;
-use crate::CmdExecute;
-use clap::Parser;
-use std::fmt;
+use crate::{
+    dedup_rows, get_writer, is_broken_pipe, join_tables, process_csv, process_csv_diff,
+    process_csv_query, process_csv_stats, process_csv_validate, read_csv_table, render_table,
+    sort_rows, write_csv_rows, write_rows, CmdExecute, ConvertOptions, DialectOptions, Expr,
+    FakeSchema, OutputShape, Schema, SortKey, WriterOptions,
+};
+use clap::{ArgAction, Parser};
+use encoding_rs::Encoding;
+use enum_dispatch::enum_dispatch;
+use std::{
+    fmt,
+    io::{IsTerminal, Write},
+    path::Path,
+};
 
 #[derive(Debug, Parser)]
+#[command(args_conflicts_with_subcommands = true)]
 pub struct CsvOpts {
-    #[arg(short,long,value_parser=verify_file)]
+    #[command(subcommand)]
+    pub cmd: Option<CsvSubCommand>,
+    #[command(flatten)]
+    pub convert: CsvConvertOpts,
+}
+
+#[derive(Debug, Parser)]
+#[enum_dispatch(CmdExecute)]
+#[allow(clippy::large_enum_variant)]
+pub enum CsvSubCommand {
+    #[command(about = "convert csv to other formats, the default when no subcommand is given")]
+    Convert(CsvConvertOpts),
+    #[command(about = "profile the columns of a csv file")]
+    Stats(CsvStatsOpts),
+    #[command(about = "check every row of a csv file against a schema")]
+    Validate(CsvValidateOpts),
+    #[command(about = "compare two csv files row by row on key columns")]
+    Diff(CsvDiffOpts),
+    #[command(about = "join two csv files on key columns")]
+    Join(CsvJoinOpts),
+    #[command(about = "sort a csv file on one or more columns")]
+    Sort(CsvSortOpts),
+    #[command(about = "drop duplicate rows of a csv file")]
+    Dedup(CsvDedupOpts),
+    #[command(about = "generate rows of fake data from a column spec")]
+    Fake(CsvFakeOpts),
+    #[command(about = "run a sql query over csv files")]
+    Query(CsvQueryOpts),
+}
+
+/// Where and how to read a csv file, shared by the csv subcommands.
+#[derive(Debug, Parser)]
+pub struct CsvInputOpts {
+    #[arg(short,long,value_parser=verify_file,default_value = "-")]
     pub input: String,
-    #[arg(long,value_parser=parse_format,default_value = "json")]
+    #[command(flatten)]
+    pub dialect: CsvDialectOpts,
+}
+
+/// How to read a csv file, for subcommands that take their inputs as arguments.
+#[derive(Debug, Parser)]
+pub struct CsvDialectOpts {
+    /// field delimiter, defaults to ',' unless sniffed
+    #[arg(short,long,value_parser=parse_ascii_char)]
+    pub delimiter: Option<u8>,
+    /// quote character, defaults to '"' unless sniffed
+    #[arg(long,value_parser=parse_ascii_char)]
+    pub quote: Option<u8>,
+    /// whether the first row is a header, use --header=false to generate column names
+    #[arg(long, action = ArgAction::Set)]
+    pub header: Option<bool>,
+    /// guess delimiter, quote and header row from the first lines of the input
+    #[arg(long)]
+    pub sniff: bool,
+    /// input encoding such as windows-1252 or utf-16le, a byte order mark is honoured either way
+    #[arg(long,value_parser=parse_encoding)]
+    pub encoding: Option<&'static Encoding>,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvConvertOpts {
+    #[command(flatten)]
+    pub source: CsvInputOpts,
+    /// more inputs or quoted glob patterns like 'players-*.csv', all merged into one output
+    #[arg(value_name = "FILES")]
+    pub files: Vec<String>,
+    /// merge inputs with different columns, cells a file doesn't have are null
+    #[arg(long)]
+    pub union: bool,
+    /// add a column naming the file each row came from, "_source" unless a name is given
+    #[arg(long, num_args = 0..=1, default_missing_value = "_source", value_name = "NAME")]
+    pub source_column: Option<String>,
+    /// input format, detected from the file extension when omitted
+    #[arg(long,value_parser=parse_input_format)]
+    pub input_format: Option<InputFormat>,
+    /// output format, defaults to json for csv input and csv otherwise
+    #[arg(long,value_parser=parse_format)]
+    pub format: Option<OutputFormat>,
+    /// output file, "-" for stdout; defaults to stdout when it isn't a terminal, output.<format> otherwise
+    #[arg(short, long)]
+    pub output: Option<String>,
+    /// only keep rows matching a condition, e.g. --where 'Position == "Goalkeeper" and `Kit Number` < 10'
+    #[arg(long = "where", value_parser = parse_where)]
+    pub filter: Option<Expr>,
+    /// only keep these columns, in this order
+    #[arg(long, value_delimiter = ',')]
+    pub select: Vec<String>,
+    /// rename a column, e.g. --rename "Kit Number=kit"
+    #[arg(long,value_parser=parse_rename)]
+    pub rename: Vec<(String, String)>,
+    /// emit rows as arrays of cells instead of objects
+    #[arg(long)]
+    pub arrays: bool,
+    /// turn `address.city` and `tags[0]` style headers into nested objects and arrays
+    #[arg(long)]
+    pub unflatten: bool,
+    /// flatten nested objects and arrays into dotted columns, e.g. for csv output
+    #[arg(long, conflicts_with = "unflatten")]
+    pub flatten: bool,
+    /// json/yaml output as an object of rows keyed by a unique column, e.g. --key-by Name
+    #[arg(long, value_name = "COLUMN")]
+    pub key_by: Option<String>,
+    /// json/yaml output as an object of row arrays grouped by a column, e.g. --group-by Position
+    #[arg(long, value_name = "COLUMN", conflicts_with = "key_by")]
+    pub group_by: Option<String>,
+    /// name of the array of tables in toml output
+    #[arg(long, default_value = "rows")]
+    pub toml_key: String,
+    /// truncate table output cells wider than this many columns, 0 to never truncate
+    #[arg(long, default_value_t = 40)]
+    pub max_width: usize,
+    /// html page for html output, the table replaces its {{content}} placeholder
+    #[arg(long,value_parser=verify_file)]
+    pub template: Option<String>,
+    /// infer numbers, booleans and nulls per cell or by scanning the whole column
+    #[arg(long,value_parser=parse_infer_mode,default_value = "off")]
+    pub infer: InferMode,
+    /// force the type of a column, e.g. --types "Kit Number=int"
+    #[arg(long,value_parser=parse_column_type)]
+    pub types: Vec<(String, ColumnType)>,
+    /// convert csv rows on this many threads, 0 for one per core; output order is kept
+    #[arg(long, default_value_t = 1)]
+    pub jobs: usize,
+    /// skip rows with the wrong number of fields or cells that don't fit their type
+    #[arg(long)]
+    pub skip_bad_rows: bool,
+    /// write the skipped rows to a csv or json (by extension) report instead of stderr
+    #[arg(long, requires = "skip_bad_rows")]
+    pub rejects: Option<String>,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvStatsOpts {
+    #[command(flatten)]
+    pub source: CsvInputOpts,
+    /// number of most frequent values to show per column
+    #[arg(long, default_value_t = 5)]
+    pub top: usize,
+    #[arg(long,value_parser=parse_stats_format,default_value = "table")]
+    pub format: StatsFormat,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvValidateOpts {
+    #[command(flatten)]
+    pub source: CsvInputOpts,
+    /// yaml column spec or json schema the rows must follow
+    #[arg(short,long,value_parser=verify_file)]
+    pub schema: String,
+    #[arg(long,value_parser=parse_stats_format,default_value = "table")]
+    pub format: StatsFormat,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvDiffOpts {
+    #[arg(value_parser=verify_file)]
+    pub old: String,
+    #[arg(value_parser=verify_file)]
+    pub new: String,
+    /// columns identifying a row in both files, e.g. --key Name or --key "Name,DOB"
+    #[arg(short, long, required = true, value_delimiter = ',')]
+    pub key: Vec<String>,
+    #[command(flatten)]
+    pub dialect: CsvDialectOpts,
+    /// table, json, or patch for a csv of the added/updated/deleted rows
+    #[arg(long,value_parser=parse_diff_format,default_value = "table")]
+    pub format: DiffFormat,
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+}
+
+/// Where and in which format the csv subcommands producing rows write them.
+#[derive(Debug, Parser)]
+pub struct CsvOutputOpts {
+    #[arg(long,value_parser=parse_format,default_value = "csv")]
     pub format: OutputFormat,
-    #[arg(short, long)] //"output.json".into()
+    /// output file, "-" for stdout; defaults to stdout when it isn't a terminal, output.<format> otherwise
+    #[arg(short, long)]
+    pub output: Option<String>,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvJoinOpts {
+    #[arg(value_parser=verify_file)]
+    pub left: String,
+    #[arg(value_parser=verify_file)]
+    pub right: String,
+    /// columns both files are joined on, e.g. --on Name
+    #[arg(long, required = true, value_delimiter = ',')]
+    pub on: Vec<String>,
+    /// inner keeps matched rows only, left keeps every row of the left file
+    #[arg(long,value_parser=parse_join_kind,default_value = "inner")]
+    pub how: JoinKind,
+    #[command(flatten)]
+    pub dialect: CsvDialectOpts,
+    #[command(flatten)]
+    pub out: CsvOutputOpts,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvSortOpts {
+    #[command(flatten)]
+    pub source: CsvInputOpts,
+    /// sort keys as column[:num|:str][:desc], e.g. --by "Kit Number:num:desc,Name"
+    #[arg(long, required = true, value_delimiter = ',')]
+    pub by: Vec<SortKey>,
+    #[command(flatten)]
+    pub out: CsvOutputOpts,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvDedupOpts {
+    #[command(flatten)]
+    pub source: CsvInputOpts,
+    /// columns that make a row a duplicate, all columns when omitted
+    #[arg(long, value_delimiter = ',')]
+    pub on: Vec<String>,
+    #[command(flatten)]
+    pub out: CsvOutputOpts,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvFakeOpts {
+    /// yaml column spec naming a generator per column, e.g. Name: { type: name }
+    #[arg(short,long,value_parser=verify_file)]
+    pub schema: String,
+    /// number of rows to generate
+    #[arg(long, default_value_t = 10)]
+    pub rows: usize,
+    /// seed for reproducible output, random when omitted
+    #[arg(long)]
+    pub seed: Option<u64>,
+    #[command(flatten)]
+    pub out: CsvOutputOpts,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvQueryOpts {
+    /// a SELECT with optional JOIN, WHERE, GROUP BY, HAVING, ORDER BY and LIMIT
+    pub sql: String,
+    /// a table the query can use, e.g. --table players=assets/juventus.csv
+    #[arg(short, long = "table", required = true, value_parser = parse_table)]
+    pub tables: Vec<(String, String)>,
+    #[command(flatten)]
+    pub dialect: CsvDialectOpts,
+    #[arg(long,value_parser=parse_format,default_value = "table")]
+    pub format: OutputFormat,
+    /// output file, "-" for stdout; defaults to stdout when it isn't a terminal, output.<format> otherwise
+    #[arg(short, long)]
     pub output: Option<String>,
-    #[arg(short, long, default_value_t = ',')]
-    pub delimiter: char,
-    #[arg(long, default_value_t = true)]
-    pub header: bool,
 }
 
 impl CmdExecute for CsvOpts {
     async fn execute(self) -> anyhow::Result<()> {
-        let output = if let Some(output) = self.output {
-            output
-        } else {
-            format!("output.{}", Into::<&'static str>::into(self.format))
+        match self.cmd {
+            Some(cmd) => cmd.execute().await,
+            None => self.convert.execute().await,
+        }
+    }
+}
+
+impl CmdExecute for CsvConvertOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let inputs = expand_inputs(&self.source.input, &self.files)?;
+        let input_format = self
+            .input_format
+            .unwrap_or_else(|| InputFormat::detect(&inputs[0]));
+        let format = self.format.unwrap_or(match input_format {
+            InputFormat::Csv => OutputFormat::Json,
+            _ => OutputFormat::Csv,
+        });
+        let output = output_path(self.output, format);
+        let opts = ConvertOptions {
+            input_format,
+            format,
+            dialect: self.source.dialect(),
+            infer: self.infer,
+            types: self.types,
+            filter: self.filter,
+            select: self.select,
+            rename: self.rename,
+            arrays: self.arrays,
+            unflatten: self.unflatten,
+            flatten: self.flatten,
+            skip_bad_rows: self.skip_bad_rows,
+            rejects: self.rejects,
+            union: self.union,
+            source_column: self.source_column,
+            jobs: self.jobs,
+            writer: WriterOptions {
+                toml_key: self.toml_key,
+                shape: match (self.key_by, self.group_by) {
+                    (Some(column), _) => OutputShape::KeyBy(column),
+                    (_, Some(column)) => OutputShape::GroupBy(column),
+                    _ => OutputShape::Rows,
+                },
+                max_width: self.max_width,
+                template: self.template,
+                headers: Vec::new(),
+            },
         };
-        process_csv(&self.input, output, self.format)?; 
+        match process_csv(&inputs, &output, &opts) {
+            Err(e) if is_broken_pipe(&e) => Ok(()),
+            res => res,
+        }
+    }
+}
+
+impl CmdExecute for CsvStatsOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let stats = process_csv_stats(&self.source.input, &self.source.dialect(), self.top)?;
+        match self.format {
+            StatsFormat::Json => println!("{}", serde_json::to_string_pretty(&stats)?),
+            StatsFormat::Table => {
+                let headers = [
+                    "column", "type", "count", "nulls", "distinct", "min", "max", "mean", "median",
+                    "top",
+                ]
+                .map(String::from);
+                let opt = |v: Option<String>| v.unwrap_or_default();
+                let rows: Vec<Vec<String>> = stats
+                    .into_iter()
+                    .map(|s| {
+                        let top: Vec<String> = s
+                            .top
+                            .iter()
+                            .map(|t| format!("{} ({})", t.value, t.count))
+                            .collect();
+                        vec![
+                            s.column,
+                            s.ty,
+                            s.count.to_string(),
+                            s.nulls.to_string(),
+                            s.distinct.to_string(),
+                            opt(s.min),
+                            opt(s.max),
+                            opt(s.mean.map(|v| format!("{:.2}", v))),
+                            opt(s.median.map(|v| v.to_string())),
+                            top.join(", "),
+                        ]
+                    })
+                    .collect();
+                print!("{}", render_table(&headers, &rows));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl CmdExecute for CsvValidateOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let schema = Schema::load(&self.schema)?;
+        let violations = process_csv_validate(&self.source.input, &self.source.dialect(), &schema)?;
+        match self.format {
+            StatsFormat::Json => println!("{}", serde_json::to_string_pretty(&violations)?),
+            StatsFormat::Table => {
+                for violation in &violations {
+                    println!("{}", violation);
+                }
+            }
+        }
+        if !violations.is_empty() {
+            anyhow::bail!(
+                "{} violations found in {}",
+                violations.len(),
+                self.source.input
+            );
+        }
         Ok(())
     }
 }
 
+impl CmdExecute for CsvDiffOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let diff = process_csv_diff(&self.old, &self.new, &self.dialect.options(), &self.key)?;
+        let mut output = get_writer(&self.output)?;
+        match self.format {
+            DiffFormat::Table => {
+                let headers = ["op", "key", "column", "old", "new"].map(String::from);
+                output.write_all(render_table(&headers, &diff.table_rows()).as_bytes())?;
+            }
+            DiffFormat::Json => {
+                serde_json::to_writer_pretty(&mut output, &diff)?;
+                writeln!(output)?;
+            }
+            DiffFormat::Patch => {
+                let mut writer = csv::Writer::from_writer(output);
+                write_csv_rows(&mut writer, &[], &diff.patch_rows())?;
+                writer.flush()?;
+                return Ok(());
+            }
+        }
+        output.flush()?;
+        Ok(())
+    }
+}
+
+impl CmdExecute for CsvJoinOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let dialect = self.dialect.options();
+        let left = read_csv_table(&self.left, &dialect)?;
+        let right = read_csv_table(&self.right, &dialect)?;
+        let joined = join_tables(left, right, &self.on, self.how)?;
+        self.out.write(&joined.headers, &joined.rows)
+    }
+}
+
+impl CmdExecute for CsvSortOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let mut table = read_csv_table(&self.source.input, &self.source.dialect())?;
+        sort_rows(&mut table.rows, &table.headers, &self.by)?;
+        self.out.write(&table.headers, &table.rows)
+    }
+}
+
+impl CmdExecute for CsvFakeOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let schema = FakeSchema::load(&self.schema)?;
+        self.out
+            .write(&schema.columns(), &schema.rows(self.rows, self.seed))
+    }
+}
+
+impl CmdExecute for CsvQueryOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let table = process_csv_query(&self.sql, &self.tables, &self.dialect.options())?;
+        let out = CsvOutputOpts {
+            format: self.format,
+            output: self.output,
+        };
+        out.write(&table.headers, &table.rows)
+    }
+}
+
+impl CmdExecute for CsvDedupOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let table = read_csv_table(&self.source.input, &self.source.dialect())?;
+        let rows = dedup_rows(table.rows, &table.headers, &self.on)?;
+        self.out.write(&table.headers, &rows)
+    }
+}
+
+impl CsvOutputOpts {
+    fn write(self, headers: &[String], rows: &[serde_json::Value]) -> anyhow::Result<()> {
+        let output = output_path(self.output, self.format);
+        match write_rows(&output, self.format, headers, rows) {
+            Err(e) if is_broken_pipe(&e) => Ok(()),
+            res => res,
+        }
+    }
+}
+
+impl CsvInputOpts {
+    pub fn dialect(&self) -> DialectOptions {
+        self.dialect.options()
+    }
+}
+
+impl CsvDialectOpts {
+    pub fn options(&self) -> DialectOptions {
+        DialectOptions {
+            delimiter: self.delimiter,
+            quote: self.quote,
+            header: self.header,
+            sniff: self.sniff,
+            encoding: self.encoding,
+        }
+    }
+}
+
+/// `-i` followed by the extra files, glob patterns expand to their matches in sorted order.
+fn expand_inputs(input: &str, files: &[String]) -> anyhow::Result<Vec<String>> {
+    let mut inputs = Vec::new();
+    if files.is_empty() || input != "-" {
+        inputs.push(input.to_string());
+    }
+    for pattern in files {
+        if Path::new(pattern).is_file() {
+            inputs.push(pattern.clone());
+            continue;
+        }
+        let matches = glob::glob(pattern)?
+            .map(|path| Ok(path?.display().to_string()))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        if matches.is_empty() {
+            anyhow::bail!("{:?} doesn't match any file", pattern);
+        }
+        inputs.extend(matches);
+    }
+    Ok(inputs)
+}
+
+/// The explicit output, else stdout when piped, else `output.<ext>`.
+fn output_path(output: Option<String>, format: OutputFormat) -> String {
+    match (output, format) {
+        (Some(output), _) => output,
+        // a table is meant to be read in the terminal
+        (None, OutputFormat::Table) => "-".into(),
+        (None, _) if !std::io::stdout().is_terminal() => "-".into(),
+        (None, format) => format!("output.{}", format.extension()),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InputFormat {
+    Csv,
+    Json,
+    Yaml,
+}
+
 #[derive(Debug, Clone, Copy)]
 pub enum OutputFormat {
     Json,
+    Jsonl,
     Yaml,
+    Csv,
+    Toml,
+    Msgpack,
+    Cbor,
+    Markdown,
+    Html,
+    Table,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum StatsFormat {
+    Table,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DiffFormat {
+    Table,
+    Json,
+    Patch,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JoinKind {
+    Inner,
+    Left,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InferMode {
+    Off,
+    Cell,
+    Column,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColumnType {
+    String,
+    Int,
+    Float,
+    Bool,
+}
+
+fn parse_stats_format(format: &str) -> Result<StatsFormat, anyhow::Error> {
+    format.parse()
+}
+
+fn parse_join_kind(kind: &str) -> Result<JoinKind, anyhow::Error> {
+    kind.parse()
+}
+
+fn parse_diff_format(format: &str) -> Result<DiffFormat, anyhow::Error> {
+    format.parse()
+}
+
+fn parse_infer_mode(mode: &str) -> Result<InferMode, anyhow::Error> {
+    mode.parse()
+}
+
+fn parse_column_type(mapping: &str) -> Result<(String, ColumnType), anyhow::Error> {
+    let (name, ty) = mapping
+        .rsplit_once('=')
+        .ok_or_else(|| anyhow::anyhow!("expected <column>=<type>"))?;
+    Ok((name.to_string(), ty.parse()?))
+}
+
+fn parse_where(expr: &str) -> Result<Expr, anyhow::Error> {
+    expr.parse()
+}
+
+fn parse_rename(mapping: &str) -> Result<(String, String), anyhow::Error> {
+    let (old, new) = mapping
+        .rsplit_once('=')
+        .ok_or_else(|| anyhow::anyhow!("expected <old>=<new>"))?;
+    Ok((old.to_string(), new.to_string()))
+}
+
+fn parse_table(table: &str) -> Result<(String, String), anyhow::Error> {
+    let (name, path) = table
+        .split_once('=')
+        .ok_or_else(|| anyhow::anyhow!("expected <name>=<file>"))?;
+    let path = verify_file(path).map_err(|e| anyhow::anyhow!("{}: {}", path, e))?;
+    Ok((name.to_string(), path))
+}
+
+fn parse_encoding(label: &str) -> Result<&'static Encoding, anyhow::Error> {
+    Encoding::for_label(label.as_bytes())
+        .ok_or_else(|| anyhow::anyhow!("unknown encoding {:?}", label))
+}
+
+fn parse_ascii_char(c: &str) -> Result<u8, anyhow::Error> {
+    match c {
+        "\\t" => Ok(b'\t'),
+        c if c.len() == 1 && c.is_ascii() => Ok(c.as_bytes()[0]),
+        _ => Err(anyhow::anyhow!("expected a single ascii character")),
+    }
+}
+
+fn parse_input_format(format: &str) -> Result<InputFormat, anyhow::Error> {
+    format.parse()
 }
 
 fn parse_format(format: &str) -> Result<OutputFormat, anyhow::Error> {
     format.parse()
 }
 
+impl InputFormat {
+    pub fn detect(input: &str) -> Self {
+        let ext = Path::new(input)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or_default();
+        match ext.to_ascii_lowercase().as_str() {
+            "json" => InputFormat::Json,
+            "yaml" | "yml" => InputFormat::Yaml,
+            _ => InputFormat::Csv,
+        }
+    }
+}
+
+impl OutputFormat {
+    /// File extension of the format, without the dot.
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Json => "json",
+            OutputFormat::Jsonl => "jsonl",
+            OutputFormat::Yaml => "yaml",
+            OutputFormat::Csv => "csv",
+            OutputFormat::Toml => "toml",
+            OutputFormat::Msgpack => "msgpack",
+            OutputFormat::Cbor => "cbor",
+            OutputFormat::Markdown => "md",
+            OutputFormat::Html => "html",
+            OutputFormat::Table => "txt",
+        }
+    }
+}
+
+impl From<InputFormat> for &'static str {
+    fn from(format: InputFormat) -> Self {
+        match format {
+            InputFormat::Csv => "csv",
+            InputFormat::Json => "json",
+            InputFormat::Yaml => "yaml",
+        }
+    }
+}
+
+impl std::str::FromStr for InputFormat {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(InputFormat::Csv),
+            "json" => Ok(InputFormat::Json),
+            "yaml" => Ok(InputFormat::Yaml),
+            _ => Err(anyhow::anyhow!("Invalid format")),
+        }
+    }
+}
+
+impl fmt::Display for InputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
 impl From<OutputFormat> for &'static str {
     fn from(format: OutputFormat) -> Self {
         match format {
             OutputFormat::Json => "json",
+            OutputFormat::Jsonl => "jsonl",
             OutputFormat::Yaml => "yaml",
+            OutputFormat::Csv => "csv",
+            OutputFormat::Toml => "toml",
+            OutputFormat::Msgpack => "msgpack",
+            OutputFormat::Cbor => "cbor",
+            OutputFormat::Markdown => "markdown",
+            OutputFormat::Html => "html",
+            OutputFormat::Table => "table",
         }
     }
 }
@@ -54,7 +720,15 @@ impl std::str::FromStr for OutputFormat {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             "json" => Ok(OutputFormat::Json),
+            "jsonl" | "ndjson" => Ok(OutputFormat::Jsonl),
             "yaml" => Ok(OutputFormat::Yaml),
+            "csv" => Ok(OutputFormat::Csv),
+            "toml" => Ok(OutputFormat::Toml),
+            "msgpack" => Ok(OutputFormat::Msgpack),
+            "cbor" => Ok(OutputFormat::Cbor),
+            "markdown" | "md" => Ok(OutputFormat::Markdown),
+            "html" => Ok(OutputFormat::Html),
+            "table" => Ok(OutputFormat::Table),
             _ => Err(anyhow::anyhow!("Invalid format")),
         }
     }
@@ -65,3 +739,141 @@ impl fmt::Display for OutputFormat {
         write!(f, "{}", Into::<&str>::into(*self))
     }
 }
+
+impl From<InferMode> for &'static str {
+    fn from(mode: InferMode) -> Self {
+        match mode {
+            InferMode::Off => "off",
+            InferMode::Cell => "cell",
+            InferMode::Column => "column",
+        }
+    }
+}
+
+impl std::str::FromStr for InferMode {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(InferMode::Off),
+            "cell" => Ok(InferMode::Cell),
+            "column" => Ok(InferMode::Column),
+            _ => Err(anyhow::anyhow!("Invalid infer mode")),
+        }
+    }
+}
+
+impl fmt::Display for InferMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl From<ColumnType> for &'static str {
+    fn from(ty: ColumnType) -> Self {
+        match ty {
+            ColumnType::String => "string",
+            ColumnType::Int => "int",
+            ColumnType::Float => "float",
+            ColumnType::Bool => "bool",
+        }
+    }
+}
+
+impl std::str::FromStr for ColumnType {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "string" => Ok(ColumnType::String),
+            "int" => Ok(ColumnType::Int),
+            "float" => Ok(ColumnType::Float),
+            "bool" => Ok(ColumnType::Bool),
+            _ => Err(anyhow::anyhow!("Invalid column type")),
+        }
+    }
+}
+
+impl fmt::Display for ColumnType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl From<StatsFormat> for &'static str {
+    fn from(format: StatsFormat) -> Self {
+        match format {
+            StatsFormat::Table => "table",
+            StatsFormat::Json => "json",
+        }
+    }
+}
+
+impl std::str::FromStr for StatsFormat {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "table" => Ok(StatsFormat::Table),
+            "json" => Ok(StatsFormat::Json),
+            _ => Err(anyhow::anyhow!("Invalid format")),
+        }
+    }
+}
+
+impl fmt::Display for StatsFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl From<JoinKind> for &'static str {
+    fn from(kind: JoinKind) -> Self {
+        match kind {
+            JoinKind::Inner => "inner",
+            JoinKind::Left => "left",
+        }
+    }
+}
+
+impl std::str::FromStr for JoinKind {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "inner" => Ok(JoinKind::Inner),
+            "left" => Ok(JoinKind::Left),
+            _ => Err(anyhow::anyhow!("Invalid join, expected inner or left")),
+        }
+    }
+}
+
+impl fmt::Display for JoinKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl From<DiffFormat> for &'static str {
+    fn from(format: DiffFormat) -> Self {
+        match format {
+            DiffFormat::Table => "table",
+            DiffFormat::Json => "json",
+            DiffFormat::Patch => "patch",
+        }
+    }
+}
+
+impl std::str::FromStr for DiffFormat {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "table" => Ok(DiffFormat::Table),
+            "json" => Ok(DiffFormat::Json),
+            "patch" => Ok(DiffFormat::Patch),
+            _ => Err(anyhow::anyhow!("Invalid format")),
+        }
+    }
+}
+
+impl fmt::Display for DiffFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
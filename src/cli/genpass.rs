@@ -1,34 +1,420 @@
-use crate::{process_genpass, CmdExecute};
+use super::{verify_file, OutputFormat};
+use crate::{
+    genpass_entropy, is_broken_pipe, process_genpass, process_genpass_batch, process_genpass_check,
+    process_passphrase, read_passwords, write_rows, CmdExecute, PassphraseOptions, PasswordPolicy,
+    Wordlist,
+};
 use anyhow::Ok;
-use clap::Parser;
-use zxcvbn::zxcvbn;
+use clap::{ArgAction, Parser};
+use enum_dispatch::enum_dispatch;
+use std::fmt;
 
 #[derive(Debug, Parser)]
+#[command(args_conflicts_with_subcommands = true)]
 pub struct GenPassOpts {
-    #[arg(short, long, default_value_t = 16)]
-    pub length: u8,
-    #[arg(long, default_value_t = true)]
+    #[command(subcommand)]
+    pub cmd: Option<GenPassSubCommand>,
+    #[command(flatten)]
+    pub generate: GenPassGenerateOpts,
+}
+
+#[derive(Debug, Parser)]
+#[enum_dispatch(CmdExecute)]
+pub enum GenPassSubCommand {
+    #[command(about = "score existing passwords with zxcvbn, one per line")]
+    Check(GenPassCheckOpts),
+}
+
+#[derive(Debug, Parser)]
+pub struct GenPassGenerateOpts {
+    /// password length, 16 unless the policy sets one
+    #[arg(short, long)]
+    pub length: Option<usize>,
+    /// leave out digits
+    #[arg(long = "no-number", action = ArgAction::SetFalse)]
     pub number: bool,
-    #[arg(long, default_value_t = true)]
+    /// leave out symbols
+    #[arg(long = "no-symbols", action = ArgAction::SetFalse)]
     pub symbols: bool,
-    #[arg(long, default_value_t = true)]
+    /// leave out uppercase letters
+    #[arg(long = "no-uppercase", action = ArgAction::SetFalse)]
     pub uppercase: bool,
-    #[arg(long, default_value_t = true)]
+    /// leave out lowercase letters
+    #[arg(long = "no-lowercase", action = ArgAction::SetFalse)]
     pub lowercase: bool,
+    /// yaml or json password policy, the options below override it
+    #[arg(long,value_parser=verify_file)]
+    pub policy: Option<String>,
+    /// draw from these characters instead of the upper/lower/number/symbol classes
+    #[arg(long)]
+    pub alphabet: Option<String>,
+    /// never use these characters, e.g. --exclude '$Il1O0'
+    #[arg(long)]
+    pub exclude: Option<String>,
+    /// at least this many uppercase letters
+    #[arg(long, value_name = "N")]
+    pub min_upper: Option<usize>,
+    /// at least this many lowercase letters
+    #[arg(long, value_name = "N")]
+    pub min_lower: Option<usize>,
+    /// at least this many digits
+    #[arg(long, value_name = "N")]
+    pub min_number: Option<usize>,
+    /// at least this many symbols
+    #[arg(long, value_name = "N")]
+    pub min_symbol: Option<usize>,
+    /// the first character must be a letter
+    #[arg(long)]
+    pub start_with_letter: bool,
+    /// no character may repeat more than this many times in a row
+    #[arg(long, value_name = "N")]
+    pub max_repeat: Option<usize>,
+    /// generate a passphrase of this many words instead of a password
+    #[arg(long, value_name = "N", conflicts_with_all = [
+        "length", "policy", "number", "symbols", "uppercase", "lowercase", "alphabet", "exclude", "min_upper", "min_lower", "min_number",
+        "min_symbol", "start_with_letter", "max_repeat",
+    ])]
+    pub words: Option<usize>,
+    /// wordlist for passphrases, one word per line or diceware "11111 word" lines
+    #[arg(long,value_parser=verify_file,requires = "words")]
+    pub wordlist: Option<String>,
+    /// text between the words of a passphrase
+    #[arg(long, default_value = "-", requires = "words")]
+    pub separator: String,
+    /// case of passphrase words: lower, title, upper or random (title or lower per word)
+    #[arg(long,value_parser=parse_word_case,default_value = "lower",requires = "words")]
+    pub case: WordCase,
+    /// append this many random digits to randomly chosen passphrase words
+    #[arg(long, default_value_t = 0, requires = "words")]
+    pub digits: usize,
+    /// how many passwords to generate
+    #[arg(short = 'n', long, default_value_t = 1)]
+    pub count: usize,
+    /// draw each password again until zxcvbn scores it at least this, from 0 to 4
+    #[arg(long, default_value_t = 0, value_parser = clap::value_parser!(u8).range(0..=4))]
+    pub min_score: u8,
+    /// text, plain (password, entropy and score per tab separated line), json or csv
+    #[arg(long,value_parser=parse_pass_format,default_value = "text")]
+    pub format: PassFormat,
+}
+
+#[derive(Debug, Parser)]
+pub struct GenPassCheckOpts {
+    /// file with one password per line, stdin by default; a terminal is prompted without echo
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+    /// words an attacker would try first, like usernames or the site name
+    #[arg(long, value_delimiter = ',')]
+    pub user_inputs: Vec<String>,
+    /// fail when a password scores below this, from 0 to 4
+    #[arg(long, default_value_t = 3, value_parser = clap::value_parser!(u8).range(0..=4))]
+    pub min_score: u8,
+    #[arg(long,value_parser=parse_check_format,default_value = "table")]
+    pub format: CheckFormat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WordCase {
+    Lower,
+    Title,
+    Upper,
+    Random,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PassFormat {
+    Text,
+    Plain,
+    Json,
+    Csv,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CheckFormat {
+    Table,
+    Json,
 }
 
 impl CmdExecute for GenPassOpts {
     async fn execute(self) -> anyhow::Result<()> {
-        let pass = process_genpass(
-            self.length,
-            self.uppercase,
-            self.lowercase,
-            self.number,
-            self.symbols,
-        )?;
-        println!("Password: {}", &pass);
-        let estimate = zxcvbn(&pass, &[]).unwrap();
-        println!("password strength is {:?}", estimate.score());
+        match self.cmd {
+            Some(cmd) => cmd.execute().await,
+            None => self.generate.execute().await,
+        }
+    }
+}
+
+impl CmdExecute for GenPassGenerateOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        if self.count == 0 {
+            anyhow::bail!("--count must be at least 1");
+        }
+        let generate: Box<dyn FnMut() -> anyhow::Result<(String, f64)>> = match self.words {
+            Some(words) => {
+                let wordlist = match &self.wordlist {
+                    Some(path) => Wordlist::load(path)?,
+                    None => Wordlist::embedded(),
+                };
+                let opts = PassphraseOptions {
+                    words,
+                    separator: self.separator,
+                    case: self.case,
+                    digits: self.digits,
+                };
+                Box::new(move || process_passphrase(&wordlist, &opts))
+            }
+            None => {
+                let policy = self.password_policy()?;
+                let length = policy.length.unwrap_or(16);
+                Box::new(move || {
+                    let pass = process_genpass(length, &policy)?;
+                    Ok((pass, genpass_entropy(length, &policy)))
+                })
+            }
+        };
+        let passwords = process_genpass_batch(self.count, self.min_score, generate)?;
+        match self.format {
+            PassFormat::Text => {
+                for pass in &passwords {
+                    println!("Password: {}", &pass.password);
+                    println!("password strength is {:?}", pass.score);
+                    println!("entropy is {:.1} bits", pass.entropy);
+                }
+            }
+            PassFormat::Plain => {
+                for pass in &passwords {
+                    println!("{}\t{:.1}\t{}", pass.password, pass.entropy, pass.score);
+                }
+            }
+            PassFormat::Json | PassFormat::Csv => {
+                let format = match self.format {
+                    PassFormat::Json => OutputFormat::Json,
+                    _ => OutputFormat::Csv,
+                };
+                let rows = passwords
+                    .iter()
+                    .map(serde_json::to_value)
+                    .collect::<Result<Vec<_>, _>>()?;
+                let headers = ["password", "entropy", "score"].map(String::from);
+                match write_rows("-", format, &headers, &rows) {
+                    Err(e) if is_broken_pipe(&e) => {}
+                    res => res?,
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl CmdExecute for GenPassCheckOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let passwords = read_passwords(&self.input)?;
+        let reports = process_genpass_check(&passwords, &self.user_inputs)?;
+        match self.format {
+            CheckFormat::Json => println!("{}", serde_json::to_string_pretty(&reports)?),
+            CheckFormat::Table => {
+                for report in &reports {
+                    println!(
+                        "line {}: score {}/4, 10^{:.1} guesses",
+                        report.line, report.score, report.guesses_log10
+                    );
+                    let times = &report.crack_times;
+                    println!(
+                        "  crack time: {} online throttled, {} online, {} offline slow hash, {} offline fast hash",
+                        times.online_throttled, times.online, times.offline_slow_hash, times.offline_fast_hash
+                    );
+                    if let Some(warning) = &report.warning {
+                        println!("  warning: {}", warning);
+                    }
+                    for suggestion in &report.suggestions {
+                        println!("  suggestion: {}", suggestion);
+                    }
+                    let patterns: Vec<String> = report
+                        .patterns
+                        .iter()
+                        .map(|p| match &p.detail {
+                            Some(detail) => {
+                                format!("{} ({}) {}..{}", p.pattern, detail, p.start, p.end)
+                            }
+                            None => format!("{} {}..{}", p.pattern, p.start, p.end),
+                        })
+                        .collect();
+                    println!("  patterns: {}", patterns.join(", "));
+                }
+            }
+        }
+        let weak = reports
+            .iter()
+            .filter(|report| report.score < self.min_score)
+            .count();
+        if weak > 0 {
+            anyhow::bail!(
+                "{} of {} passwords scored below {}",
+                weak,
+                reports.len(),
+                self.min_score
+            );
+        }
+        Ok(())
+    }
+}
+
+impl GenPassGenerateOpts {
+    /// The policy file, if any, with the command line options on top.
+    fn password_policy(&self) -> anyhow::Result<PasswordPolicy> {
+        let mut policy = match &self.policy {
+            Some(path) => PasswordPolicy::load(path)?,
+            None => PasswordPolicy::default(),
+        };
+        policy.uppercase &= self.uppercase;
+        policy.lowercase &= self.lowercase;
+        policy.number &= self.number;
+        policy.symbols &= self.symbols;
+        policy.length = self.length.or(policy.length);
+        if let Some(alphabet) = &self.alphabet {
+            policy.alphabet = Some(alphabet.clone());
+        }
+        if let Some(exclude) = &self.exclude {
+            policy.exclude.push_str(exclude);
+        }
+        policy.min_upper = self.min_upper.or(policy.min_upper);
+        policy.min_lower = self.min_lower.or(policy.min_lower);
+        policy.min_number = self.min_number.or(policy.min_number);
+        policy.min_symbol = self.min_symbol.or(policy.min_symbol);
+        policy.start_with_letter |= self.start_with_letter;
+        policy.max_repeat = self.max_repeat.or(policy.max_repeat);
+        Ok(policy)
+    }
+}
+
+fn parse_pass_format(format: &str) -> Result<PassFormat, anyhow::Error> {
+    format.parse()
+}
+
+fn parse_check_format(format: &str) -> Result<CheckFormat, anyhow::Error> {
+    format.parse()
+}
+
+fn parse_word_case(case: &str) -> Result<WordCase, anyhow::Error> {
+    case.parse()
+}
+
+impl From<WordCase> for &'static str {
+    fn from(case: WordCase) -> Self {
+        match case {
+            WordCase::Lower => "lower",
+            WordCase::Title => "title",
+            WordCase::Upper => "upper",
+            WordCase::Random => "random",
+        }
+    }
+}
+
+impl std::str::FromStr for WordCase {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lower" => Ok(WordCase::Lower),
+            "title" => Ok(WordCase::Title),
+            "upper" => Ok(WordCase::Upper),
+            "random" => Ok(WordCase::Random),
+            _ => Err(anyhow::anyhow!("Invalid case")),
+        }
+    }
+}
+
+impl fmt::Display for WordCase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl From<PassFormat> for &'static str {
+    fn from(format: PassFormat) -> Self {
+        match format {
+            PassFormat::Text => "text",
+            PassFormat::Plain => "plain",
+            PassFormat::Json => "json",
+            PassFormat::Csv => "csv",
+        }
+    }
+}
+
+impl std::str::FromStr for PassFormat {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(PassFormat::Text),
+            "plain" => Ok(PassFormat::Plain),
+            "json" => Ok(PassFormat::Json),
+            "csv" => Ok(PassFormat::Csv),
+            _ => Err(anyhow::anyhow!("Invalid format")),
+        }
+    }
+}
+
+impl fmt::Display for PassFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl From<CheckFormat> for &'static str {
+    fn from(format: CheckFormat) -> Self {
+        match format {
+            CheckFormat::Table => "table",
+            CheckFormat::Json => "json",
+        }
+    }
+}
+
+impl std::str::FromStr for CheckFormat {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "table" => Ok(CheckFormat::Table),
+            "json" => Ok(CheckFormat::Json),
+            _ => Err(anyhow::anyhow!("Invalid format")),
+        }
+    }
+}
+
+impl fmt::Display for CheckFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_negated_classes() -> anyhow::Result<()> {
+        let opts = GenPassOpts::try_parse_from(["genpass", "--no-symbols", "-l", "300"])?;
+        let policy = opts.generate.password_policy()?;
+        assert!(!policy.symbols && policy.number && policy.uppercase && policy.lowercase);
+        assert_eq!(policy.length, Some(300));
+        let opts = GenPassOpts::try_parse_from(["genpass", "-n", "3", "--format", "csv"])?;
+        assert_eq!(
+            (opts.generate.count, opts.generate.format),
+            (3, PassFormat::Csv)
+        );
+        assert!(GenPassOpts::try_parse_from(["genpass", "--format", "xml"]).is_err());
+        assert!(GenPassOpts::try_parse_from(["genpass", "--words", "4", "--no-number"]).is_err());
+
+        let opts =
+            GenPassOpts::try_parse_from(["genpass", "check", "--user-inputs", "arya,stark"])?;
+        let Some(GenPassSubCommand::Check(check)) = opts.cmd else {
+            panic!("expected the check subcommand");
+        };
+        assert_eq!(check.user_inputs, ["arya", "stark"]);
+        assert_eq!(
+            (check.input.as_str(), check.min_score, check.format),
+            ("-", 3, CheckFormat::Table)
+        );
+        assert!(GenPassOpts::try_parse_from(["genpass", "check", "--format", "csv"]).is_err());
+        assert!(GenPassOpts::try_parse_from(["genpass", "check", "--min-score", "5"]).is_err());
         Ok(())
     }
 }
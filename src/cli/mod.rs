@@ -19,8 +19,9 @@ pub struct Opts {
 
 #[derive(Debug, Parser)]
 #[enum_dispatch(CmdExecute)]
+#[allow(clippy::large_enum_variant)]
 pub enum SubCommand {
-    #[command(name = "csv", about = "convert csv to other formats")]
+    #[command(name = "csv", about = "convert and inspect csv files")]
     Csv(CsvOpts),
     #[command(name = "genpass", about = "generate password")]
     Genpass(GenPassOpts),
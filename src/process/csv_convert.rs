@@ -1,36 +1,613 @@
-use crate::cli::OutputFormat;
-use anyhow::Result;
-use csv::Reader;
-use serde::{Deserialize, Serialize};
-use serde_json::Value;
-use std::fs;
-
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(rename_all = "PascalCase")]
-pub struct Player {
-    pub name: String,
-    pub position: String,
-    #[serde(rename = "DOB")]
-    pub dob: String,
-    pub nationality: String,
-    #[serde(rename = "Kit Number")]
-    pub kit: u8,
-}
-
-pub fn process_csv(input: &str, output: String, format: OutputFormat) -> Result<()> {
-    let mut reader = Reader::from_path(input)?;
-    let headers = reader.headers()?.clone();
-    let mut res = Vec::with_capacity(128);
-    for i in reader.records() {
-        let record = i?;
-        let player: Value = headers.iter().zip(record.iter()).collect::<Value>();
-        res.push(player);
-    }
-    let content = match format {
-        OutputFormat::Json => serde_json::to_string_pretty(&res)?,
-        OutputFormat::Yaml => serde_yaml::to_string(&res)?,
+use crate::cli::{ColumnType, InferMode, InputFormat, OutputFormat};
+use crate::process::csv_filter::{project_row, Expr};
+use crate::process::csv_nested::{flatten_row, unflatten_row};
+use crate::process::csv_parallel::convert_parallel;
+use crate::process::csv_rejects::{describe_csv_error, BadRow, Rejects};
+use crate::process::csv_sniff::{Dialect, DialectOptions};
+use crate::process::csv_types::RowTyper;
+use crate::process::row_writer::{row_writer, RowWriter, WriterOptions};
+use crate::utils::{get_reader, get_writer, input_name};
+use anyhow::{bail, Result};
+use csv::{Position, Reader, ReaderBuilder, StringRecord};
+use serde_json::{Map, Value};
+use std::{collections::HashSet, io::Read};
+
+#[derive(Debug, Clone)]
+pub struct ConvertOptions {
+    pub input_format: InputFormat,
+    pub format: OutputFormat,
+    pub dialect: DialectOptions,
+    pub infer: InferMode,
+    pub types: Vec<(String, ColumnType)>,
+    /// only keep rows matching this condition
+    pub filter: Option<Expr>,
+    /// only keep these columns, in this order
+    pub select: Vec<String>,
+    /// `old=new` column renames, applied after `select`
+    pub rename: Vec<(String, String)>,
+    /// emit every row as an array of cells instead of an object
+    pub arrays: bool,
+    /// nest `address.city` / `tags[0]` style keys into objects and arrays
+    pub unflatten: bool,
+    /// flatten nested objects and arrays into dotted keys
+    pub flatten: bool,
+    /// keep converting past malformed rows instead of failing on the first one
+    pub skip_bad_rows: bool,
+    /// where to write the skipped rows, as csv or json depending on the extension
+    pub rejects: Option<String>,
+    /// allow inputs with different columns, rows get the union of all of them
+    pub union: bool,
+    /// add a column naming the input each row came from
+    pub source_column: Option<String>,
+    /// threads converting csv rows, 1 converts on the calling thread and 0 uses every core
+    pub jobs: usize,
+    pub writer: WriterOptions,
+}
+
+/// An opened csv input: its name, reader and header row.
+pub type CsvSource<'a> = (&'a str, Reader<Box<dyn Read>>, Vec<String>);
+
+/// Convert one or more inputs of the same format into a single output.
+pub fn process_csv(inputs: &[String], output: &str, opts: &ConvertOptions) -> Result<()> {
+    opts.writer.check(opts.format)?;
+    let mut rejects = Rejects::new(opts.skip_bad_rows);
+    let writer = match opts.input_format {
+        InputFormat::Csv => {
+            let mut sources = Vec::with_capacity(inputs.len());
+            for input in inputs {
+                let (reader, dialect) = opts.dialect.resolve(get_reader(input)?)?;
+                let mut reader = csv_reader(reader, dialect);
+                let headers = csv_headers(&mut reader)?;
+                sources.push((input.as_str(), reader, headers));
+            }
+            let file_headers = sources.iter().map(|(input, _, h)| (*input, h.as_slice()));
+            let headers = merge_headers(file_headers, opts.union)?;
+            opts.check_columns(&headers)?;
+            let writer_opts = WriterOptions {
+                headers: opts.output_headers(&headers),
+                ..opts.writer.clone()
+            };
+            let mut writer = row_writer(opts.format, get_writer(output)?, &writer_opts);
+            // a pool of one thread only adds overhead
+            let jobs = match opts.jobs {
+                0 => std::thread::available_parallelism().map_or(1, usize::from),
+                jobs => jobs,
+            };
+            if jobs == 1 {
+                convert_sources(sources, &headers, opts, writer.as_mut(), &mut rejects)?;
+            } else {
+                convert_parallel(sources, &headers, opts, writer.as_mut(), &mut rejects)?;
+            }
+            writer
+        }
+        format => {
+            let mut rows = Vec::new();
+            for input in inputs {
+                let reader = opts.dialect.decode(get_reader(input)?);
+                let read = read_rows(reader, format)?.into_iter().enumerate();
+                rows.extend(read.map(|(i, row)| (input, i + 1, row)));
+            }
+            let headers = object_keys(rows.iter().map(|(_, _, row)| row));
+            opts.check_columns(&headers)?;
+            // flattening replaces nested keys, only the flat ones are known up front
+            let nested = |h: &String| {
+                rows.iter()
+                    .any(|(_, _, row)| row[h.as_str()].is_array() || row[h.as_str()].is_object())
+            };
+            let known: Vec<String> = headers
+                .iter()
+                .filter(|h| !opts.flatten || !nested(h))
+                .cloned()
+                .collect();
+            let writer_opts = WriterOptions {
+                headers: opts.output_headers(&known),
+                ..opts.writer.clone()
+            };
+            let mut writer = row_writer(opts.format, get_writer(output)?, &writer_opts);
+            let scan = rows.iter().map(|(_, _, row)| row);
+            let typer = RowTyper::new(&headers, opts.infer, &opts.types, scan)?;
+            for (input, i, mut row) in rows {
+                if let Err(e) = typer.apply(&mut row) {
+                    // documents have no csv lines, the row number stands in for one
+                    let mut pos = Position::new();
+                    pos.set_line(i as u64).set_record(i as u64);
+                    rejects.reject(BadRow::at_cell(input, &pos, &headers, &e.column, e.reason))?;
+                    continue;
+                }
+                emit(writer.as_mut(), opts.tag_source(row, input), opts)?;
+            }
+            writer
+        }
     };
-    // let json = serde_json::to_string_pretty(&res)?;
-    fs::write(output, content)?;
+    writer.finish()?;
+    rejects.finish(opts.rejects.as_deref())
+}
+
+/// Convert csv rows one at a time on the calling thread.
+fn convert_sources(
+    sources: Vec<CsvSource>,
+    headers: &[String],
+    opts: &ConvertOptions,
+    writer: &mut dyn RowWriter,
+    rejects: &mut Rejects,
+) -> Result<()> {
+    let column_wide = opts.infer == InferMode::Column;
+    let typer = RowTyper::new(headers, opts.infer, &opts.types, &[])?;
+    // column-wide inference has to see every row before the first one is written
+    let mut pending = Vec::new();
+    for (input, mut reader, file_headers) in sources {
+        let aligned = file_headers == headers;
+        for record in reader.records() {
+            let record = match record {
+                Ok(record) => record,
+                Err(e) => {
+                    rejects.reject(BadRow::from_csv_error(input, e, &file_headers)?)?;
+                    continue;
+                }
+            };
+            let pos = record.position().cloned().unwrap_or_else(Position::new);
+            let mut row = record_to_row(&file_headers, &record);
+            if !aligned {
+                row = align_row(row, headers);
+            }
+            if column_wide {
+                pending.push((input, pos, row));
+            } else if let Err(e) = typer.apply(&mut row) {
+                let bad = BadRow::at_cell(input, &pos, headers, &e.column, e.reason);
+                rejects.reject(bad)?;
+            } else {
+                emit(writer, opts.tag_source(row, input), opts)?;
+            }
+        }
+    }
+    if column_wide {
+        let scan = pending.iter().map(|(_, _, row)| row);
+        let typer = RowTyper::new(headers, opts.infer, &opts.types, scan)?;
+        for (input, pos, mut row) in pending {
+            match typer.apply(&mut row) {
+                Ok(()) => emit(writer, opts.tag_source(row, input), opts)?,
+                Err(e) => {
+                    rejects.reject(BadRow::at_cell(input, &pos, headers, &e.column, e.reason))?
+                }
+            }
+        }
+    }
     Ok(())
 }
+
+/// The columns of all inputs, which have to agree unless `union` is set.
+fn merge_headers<'a>(
+    mut files: impl Iterator<Item = (&'a str, &'a [String])>,
+    union: bool,
+) -> Result<Vec<String>> {
+    let Some((first, headers)) = files.next() else {
+        return Ok(Vec::new());
+    };
+    let mut merged = headers.to_vec();
+    for (input, headers) in files {
+        if union {
+            for header in headers {
+                if !merged.contains(header) {
+                    merged.push(header.clone());
+                }
+            }
+        } else if headers.len() != merged.len() || headers.iter().any(|h| !merged.contains(h)) {
+            bail!(
+                "{}: columns {:?} don't match {:?} of {}, try --union",
+                input_name(input),
+                headers,
+                merged,
+                input_name(first)
+            );
+        }
+    }
+    Ok(merged)
+}
+
+/// Put the cells of a row in `headers` order, columns it doesn't have are null.
+pub fn align_row(mut row: Value, headers: &[String]) -> Value {
+    let Some(obj) = row.as_object_mut() else {
+        return row;
+    };
+    headers
+        .iter()
+        .map(|h| (h.clone(), obj.remove(h).unwrap_or(Value::Null)))
+        .collect::<Map<_, _>>()
+        .into()
+}
+
+impl ConvertOptions {
+    /// Make sure `--where`, `--select` and `--rename` only mention existing csv columns.
+    fn check_columns(&self, headers: &[String]) -> Result<()> {
+        let headers = [headers, self.source_column.as_slice()].concat();
+        let filter_columns = self.filter.iter().flat_map(Expr::columns);
+        let columns = self.select.iter().map(String::as_str);
+        let renamed = self.rename.iter().map(|(old, _)| old.as_str());
+        for column in filter_columns.chain(columns).chain(renamed) {
+            if !headers.iter().any(|h| h == column) {
+                bail!("unknown column {:?}, expected one of {:?}", column, headers);
+            }
+        }
+        Ok(())
+    }
+
+    /// Columns of the output rows as far as the input header tells, nested ones aren't known
+    /// until the rows are unflattened.
+    fn output_headers(&self, headers: &[String]) -> Vec<String> {
+        if self.unflatten && !self.flatten {
+            return Vec::new();
+        }
+        let row = headers
+            .iter()
+            .chain(&self.source_column)
+            .map(|h| (h.clone(), Value::Null))
+            .collect();
+        match project_row(Value::Object(row), &self.select, &self.rename) {
+            Value::Object(obj) => obj.into_iter().map(|(column, _)| column).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Record which input a row came from, when asked to.
+    pub fn tag_source(&self, mut row: Value, input: &str) -> Value {
+        if let (Some(column), Some(obj)) = (&self.source_column, row.as_object_mut()) {
+            obj.insert(column.clone(), input_name(input).into());
+        }
+        row
+    }
+}
+
+fn emit(writer: &mut dyn RowWriter, row: Value, opts: &ConvertOptions) -> Result<()> {
+    match transform(row, opts)? {
+        Some(row) => writer.write_row(&row),
+        None => Ok(()),
+    }
+}
+
+/// Filter, project and reshape a row for output, `None` when `--where` drops it.
+pub fn transform(row: Value, opts: &ConvertOptions) -> Result<Option<Value>> {
+    if opts
+        .filter
+        .as_ref()
+        .is_some_and(|filter| !filter.eval(&row))
+    {
+        return Ok(None);
+    }
+    let row = project_row(row, &opts.select, &opts.rename);
+    let row = if opts.unflatten {
+        unflatten_row(row)?
+    } else {
+        row
+    };
+    let row = if opts.flatten { flatten_row(row) } else { row };
+    if opts.arrays
+        && !matches!(
+            opts.format,
+            OutputFormat::Csv | OutputFormat::Markdown | OutputFormat::Html | OutputFormat::Table
+        )
+    {
+        Ok(Some(row_to_array(row)))
+    } else {
+        Ok(Some(row))
+    }
+}
+
+pub fn csv_reader<R: Read>(reader: R, dialect: Dialect) -> Reader<R> {
+    ReaderBuilder::new()
+        .delimiter(dialect.delimiter)
+        .quote(dialect.quote)
+        .has_headers(dialect.has_header)
+        .from_reader(reader)
+}
+
+/// The header row, or `column1..N` for headerless files.
+pub fn csv_headers<R: Read>(reader: &mut Reader<R>) -> Result<Vec<String>> {
+    let headers = if reader.has_headers() {
+        reader.headers()?.iter().map(String::from).collect()
+    } else {
+        let width = reader.headers()?.len();
+        (1..=width).map(|i| format!("column{}", i)).collect()
+    };
+    Ok(headers)
+}
+
+pub fn record_to_row(headers: &[String], record: &StringRecord) -> Value {
+    headers
+        .iter()
+        .map(String::as_str)
+        .zip(record.iter())
+        .collect::<Value>()
+}
+
+/// A whole csv file read into memory, each row an object keyed by header.
+#[derive(Debug)]
+pub struct CsvTable {
+    pub headers: Vec<String>,
+    pub rows: Vec<Value>,
+}
+
+/// Read a whole csv file, for commands that need to look at all rows at once.
+pub fn read_csv_table(input: &str, dialect: &DialectOptions) -> Result<CsvTable> {
+    let (reader, dialect) = dialect.resolve(get_reader(input)?)?;
+    let mut reader = csv_reader(reader, dialect);
+    let headers = csv_headers(&mut reader)?;
+    let rows = reader
+        .records()
+        .map(|record| {
+            let record = record.map_err(|e| describe_csv_error(input, e, &headers))?;
+            Ok(record_to_row(&headers, &record))
+        })
+        .collect::<Result<_>>()?;
+    Ok(CsvTable { headers, rows })
+}
+
+/// Read a whole json/yaml document, or csv with the default dialect, as a list of rows.
+fn read_rows(reader: impl Read, format: InputFormat) -> Result<Vec<Value>> {
+    let doc: Value = match format {
+        InputFormat::Json => serde_json::from_reader(reader)?,
+        InputFormat::Yaml => serde_yaml::from_reader(reader)?,
+        InputFormat::Csv => unreachable!("csv input is read record by record"),
+    };
+    match doc {
+        Value::Array(rows) => Ok(rows),
+        _ => bail!("expected an array of objects at the top level of the {format} input"),
+    }
+}
+
+/// Keys of all object rows, in first-seen order.
+fn object_keys<'a>(rows: impl IntoIterator<Item = &'a Value>) -> Vec<String> {
+    let mut seen = HashSet::new();
+    rows.into_iter()
+        .filter_map(Value::as_object)
+        .flat_map(Map::keys)
+        .filter(|key| seen.insert(key.as_str()))
+        .cloned()
+        .collect()
+}
+
+fn row_to_array(row: Value) -> Value {
+    match row {
+        Value::Object(obj) => Value::Array(obj.into_iter().map(|(_, v)| v).collect()),
+        row => row,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::row_writer::write_csv_rows;
+
+    fn rows_to_csv(rows: &[Value]) -> Result<String> {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        write_csv_rows(&mut writer, &[], rows)?;
+        Ok(String::from_utf8(writer.into_inner()?)?)
+    }
+
+    #[test]
+    fn test_json_to_csv_union_header() -> Result<()> {
+        let input = r#"[{"Name": "Buffon", "Kit Number": 77}, {"Name": "Perin", "Position": "Goalkeeper", "Active": true}]"#;
+        let rows = read_rows(input.as_bytes(), InputFormat::Json)?;
+        let csv = rows_to_csv(&rows)?;
+        assert_eq!(
+            csv,
+            "Name,Kit Number,Position,Active\nBuffon,77,,\nPerin,,Goalkeeper,true\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_yaml_to_csv() -> Result<()> {
+        let input = "- Name: Buffon\n  Position: Goalkeeper\n- Name: Dybala\n  Position: Forward\n";
+        let rows = read_rows(input.as_bytes(), InputFormat::Yaml)?;
+        let csv = rows_to_csv(&rows)?;
+        assert_eq!(csv, "Name,Position\nBuffon,Goalkeeper\nDybala,Forward\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_nested_value_rejected() {
+        let input = r#"[{"Name": "Buffon", "address": {"city": "Turin"}}]"#;
+        let rows = read_rows(input.as_bytes(), InputFormat::Json).unwrap();
+        let err = rows_to_csv(&rows).unwrap_err();
+        assert!(err.to_string().contains("row 1, column \"address\""));
+    }
+
+    #[test]
+    fn test_headerless_csv() -> Result<()> {
+        let dialect = Dialect {
+            delimiter: b';',
+            has_header: false,
+            ..Default::default()
+        };
+        let mut reader = csv_reader("Buffon;77\nPerin;37\n".as_bytes(), dialect);
+        let headers = csv_headers(&mut reader)?;
+        assert_eq!(headers, ["column1", "column2"]);
+        let rows = reader
+            .records()
+            .map(|record| Ok(record_to_row(&headers, &record?)))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(
+            rows[1],
+            serde_json::json!({"column1": "Perin", "column2": "37"})
+        );
+        assert_eq!(
+            row_to_array(rows[1].clone()),
+            serde_json::json!(["Perin", "37"])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_headers() -> Result<()> {
+        let a = ["Name".to_string(), "Kit".to_string()];
+        let b = ["Kit".to_string(), "Name".to_string(), "Age".to_string()];
+        let files = || [("a.csv", &a[..]), ("b.csv", &b[..])].into_iter();
+        let err = merge_headers(files(), false).unwrap_err();
+        assert!(err.to_string().starts_with("b.csv: columns"));
+        let headers = merge_headers(files(), true)?;
+        assert_eq!(headers, ["Name", "Kit", "Age"]);
+        assert_eq!(
+            align_row(serde_json::json!({"Kit": "1", "Name": "Perin"}), &headers),
+            serde_json::json!({"Name": "Perin", "Kit": "1", "Age": null})
+        );
+        Ok(())
+    }
+
+    fn convert_in_memory(input: &str, opts: &ConvertOptions) -> Result<String> {
+        let reader: Box<dyn Read> = Box::new(std::io::Cursor::new(input.to_string()));
+        let mut reader = csv_reader(reader, Dialect::default());
+        let headers = csv_headers(&mut reader)?;
+        let sources = vec![("-", reader, headers.clone())];
+        let mut buf = Vec::new();
+        let mut writer = row_writer(opts.format, Box::new(&mut buf), &opts.writer);
+        let mut rejects = Rejects::new(true);
+        if opts.jobs == 1 {
+            convert_sources(sources, &headers, opts, writer.as_mut(), &mut rejects)?;
+        } else {
+            convert_parallel(sources, &headers, opts, writer.as_mut(), &mut rejects)?;
+        }
+        writer.finish()?;
+        Ok(String::from_utf8(buf)?)
+    }
+
+    #[test]
+    fn test_parallel_matches_sequential() -> Result<()> {
+        let mut input = String::from("Name,Kit,Score\n");
+        for i in 0..10_000 {
+            let score = if i % 7 == 0 {
+                "n/a".to_string()
+            } else {
+                format!("{}.5", i)
+            };
+            input.push_str(&format!("player{},{},{}\n", i, i % 99, score));
+        }
+        input.push_str("broken\n");
+        for (format, infer) in [
+            (OutputFormat::Json, InferMode::Cell),
+            (OutputFormat::Yaml, InferMode::Column),
+            (OutputFormat::Csv, InferMode::Off),
+        ] {
+            let mut opts = ConvertOptions {
+                input_format: InputFormat::Csv,
+                format,
+                dialect: DialectOptions::default(),
+                infer,
+                types: vec![("Kit".into(), ColumnType::Int)],
+                filter: Some("Kit < 50".parse()?),
+                select: vec![],
+                rename: vec![],
+                arrays: false,
+                unflatten: false,
+                flatten: false,
+                skip_bad_rows: true,
+                rejects: None,
+                union: false,
+                source_column: None,
+                jobs: 1,
+                writer: WriterOptions::default(),
+            };
+            let sequential = convert_in_memory(&input, &opts)?;
+            opts.jobs = 4;
+            assert_eq!(convert_in_memory(&input, &opts)?, sequential);
+        }
+        Ok(())
+    }
+
+    /// Run `process_csv` over `input` written to a temporary file named `name`.
+    fn convert_file(name: &str, input: &str, opts: &ConvertOptions) -> Result<String> {
+        let dir = std::env::temp_dir().join(format!("rcli-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let (path, output) = (dir.join(name), dir.join(format!("{}.out", name)));
+        std::fs::write(&path, input)?;
+        let output = output.to_string_lossy().into_owned();
+        process_csv(&[path.to_string_lossy().into_owned()], &output, opts)?;
+        Ok(std::fs::read_to_string(output)?)
+    }
+
+    fn json_to_csv() -> ConvertOptions {
+        ConvertOptions {
+            input_format: InputFormat::Json,
+            format: OutputFormat::Csv,
+            dialect: DialectOptions::default(),
+            infer: InferMode::Off,
+            types: vec![],
+            filter: None,
+            select: vec![],
+            rename: vec![],
+            arrays: false,
+            unflatten: false,
+            flatten: false,
+            skip_bad_rows: false,
+            rejects: None,
+            union: false,
+            source_column: None,
+            jobs: 1,
+            writer: WriterOptions::default(),
+        }
+    }
+
+    #[test]
+    fn test_json_to_csv_flatten() -> Result<()> {
+        let opts = ConvertOptions {
+            flatten: true,
+            ..json_to_csv()
+        };
+        let input = r#"[{"a": 1, "b": {"c": 2}}, {"d": [1, 2]}]"#;
+        assert_eq!(
+            convert_file("flatten.json", input, &opts)?,
+            "a,b.c,d[0],d[1]\n1,2,,\n,,1,2\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_json_input_types_and_columns() -> Result<()> {
+        let input =
+            r#"[{"Name": "Buffon", "Kit Number": "77"}, {"Name": "Perin", "Kit Number": "x"}]"#;
+        let rows = read_rows(input.as_bytes(), InputFormat::Json)?;
+        let headers = object_keys(&rows);
+        assert_eq!(headers, ["Name", "Kit Number"]);
+        let types = [("Kit Number".to_string(), ColumnType::Int)];
+        let typer = RowTyper::new(&headers, InferMode::Off, &types, &[])?;
+        let mut first = rows[0].clone();
+        typer.apply(&mut first)?;
+        assert_eq!(first["Kit Number"], 77);
+        assert!(typer.apply(&mut rows[1].clone()).is_err());
+        let unknown = [("Number".to_string(), ColumnType::Int)];
+        assert!(RowTyper::new(&headers, InferMode::Off, &unknown, &[]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_csv_round_trip() -> Result<()> {
+        let input = "Name,Position\nBuffon,Goalkeeper\n";
+        let mut reader = csv_reader(input.as_bytes(), Dialect::default());
+        let headers = csv_headers(&mut reader)?;
+        let rows = reader
+            .records()
+            .map(|record| Ok(record_to_row(&headers, &record?)))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(rows_to_csv(&rows)?, input);
+        Ok(())
+    }
+
+    #[test]
+    fn test_json_bad_cells_are_rejected() -> Result<()> {
+        let input = r#"[{"Name": "Buffon", "Kit": "77"}, {"Name": "Perin", "Kit": "x"}]"#;
+        let opts = ConvertOptions {
+            types: vec![("Kit".into(), ColumnType::Int)],
+            ..json_to_csv()
+        };
+        let err = convert_file("bad.json", input, &opts).unwrap_err();
+        assert!(err.to_string().contains("line 2"), "{}", err);
+        let opts = ConvertOptions {
+            skip_bad_rows: true,
+            ..opts
+        };
+        assert_eq!(
+            convert_file("skip.json", input, &opts)?,
+            "Name,Kit\nBuffon,77\n"
+        );
+        Ok(())
+    }
+}
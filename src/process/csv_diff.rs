@@ -0,0 +1,232 @@
+use crate::process::csv_convert::{read_csv_table, CsvTable};
+use crate::process::csv_filter::cell_text;
+use crate::process::csv_sniff::DialectOptions;
+use anyhow::{bail, Result};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+/// Differences between two versions of a csv table, matched up by key columns.
+#[derive(Debug, Default, Serialize, PartialEq)]
+pub struct CsvDiff {
+    pub key: Vec<String>,
+    /// columns only found in the new file
+    pub columns_added: Vec<String>,
+    /// columns only found in the old file
+    pub columns_removed: Vec<String>,
+    pub added: Vec<Value>,
+    pub removed: Vec<Value>,
+    pub changed: Vec<RowChange>,
+}
+
+/// The cells that changed in a row found in both files.
+#[derive(Debug, Serialize, PartialEq)]
+pub struct RowChange {
+    /// values of the key columns
+    pub key: Map<String, Value>,
+    pub cells: Vec<CellChange>,
+    /// the full row from the new file
+    #[serde(skip)]
+    pub row: Value,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct CellChange {
+    pub column: String,
+    pub old: String,
+    pub new: String,
+}
+
+impl CsvDiff {
+    pub fn is_empty(&self) -> bool {
+        self.columns_added.is_empty()
+            && self.columns_removed.is_empty()
+            && self.added.is_empty()
+            && self.removed.is_empty()
+            && self.changed.is_empty()
+    }
+
+    /// One line per added/removed row and per changed cell: op, key, column, old, new.
+    pub fn table_rows(&self) -> Vec<Vec<String>> {
+        let key_text = |row: &Value| {
+            self.key
+                .iter()
+                .map(|k| cell_text(&row[k]))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let mut rows = Vec::new();
+        for column in &self.columns_added {
+            rows.push(vec!["+col".into(), String::new(), column.clone()]);
+        }
+        for column in &self.columns_removed {
+            rows.push(vec!["-col".into(), String::new(), column.clone()]);
+        }
+        for row in &self.removed {
+            rows.push(vec!["-".into(), key_text(row)]);
+        }
+        for row in &self.added {
+            rows.push(vec!["+".into(), key_text(row)]);
+        }
+        for change in &self.changed {
+            let key = key_text(&Value::Object(change.key.clone()));
+            for cell in &change.cells {
+                rows.push(vec![
+                    "~".into(),
+                    key.clone(),
+                    cell.column.clone(),
+                    cell.old.clone(),
+                    cell.new.clone(),
+                ]);
+            }
+        }
+        rows
+    }
+
+    /// Rows that turn the old file into the new one, marked by an `_op` column of
+    /// `add`, `update` (the full new row) or `delete` (the full old row).
+    pub fn patch_rows(&self) -> Vec<Value> {
+        let with_op = |op: &str, row: &Value| {
+            let mut patch = Map::new();
+            patch.insert("_op".into(), op.into());
+            if let Some(obj) = row.as_object() {
+                patch.extend(obj.clone());
+            }
+            Value::Object(patch)
+        };
+        let added = self.added.iter().map(|row| with_op("add", row));
+        let changed = self.changed.iter().map(|c| with_op("update", &c.row));
+        let removed = self.removed.iter().map(|row| with_op("delete", row));
+        added.chain(changed).chain(removed).collect()
+    }
+}
+
+/// Compare two csv files row by row, rows are matched on the values of the `key` columns.
+pub fn process_csv_diff(
+    old: &str,
+    new: &str,
+    dialect: &DialectOptions,
+    key: &[String],
+) -> Result<CsvDiff> {
+    let old_table = read_csv_table(old, dialect)?;
+    let new_table = read_csv_table(new, dialect)?;
+    let old_rows = index_rows(old, &old_table, key)?;
+    let new_rows = index_rows(new, &new_table, key)?;
+
+    let shared: Vec<&String> = new_table
+        .headers
+        .iter()
+        .filter(|h| old_table.headers.contains(h) && !key.contains(h))
+        .collect();
+    let mut diff = CsvDiff {
+        key: key.to_vec(),
+        columns_added: missing_from(&new_table.headers, &old_table.headers),
+        columns_removed: missing_from(&old_table.headers, &new_table.headers),
+        ..Default::default()
+    };
+    for (row_key, row) in keyed(&old_table, key) {
+        if !new_rows.contains_key(&row_key) {
+            diff.removed.push(row.clone());
+        }
+    }
+    for (row_key, row) in keyed(&new_table, key) {
+        let Some(old_row) = old_rows.get(&row_key) else {
+            diff.added.push(row.clone());
+            continue;
+        };
+        let cells: Vec<CellChange> = shared
+            .iter()
+            .filter_map(|&column| {
+                let (old, new) = (cell_text(&old_row[column]), cell_text(&row[column]));
+                (old != new).then(|| CellChange {
+                    column: column.clone(),
+                    old,
+                    new,
+                })
+            })
+            .collect();
+        if !cells.is_empty() {
+            diff.changed.push(RowChange {
+                key: key.iter().map(|k| (k.clone(), row[k].clone())).collect(),
+                cells,
+                row: row.clone(),
+            });
+        }
+    }
+    Ok(diff)
+}
+
+fn missing_from(headers: &[String], other: &[String]) -> Vec<String> {
+    headers
+        .iter()
+        .filter(|h| !other.contains(h))
+        .cloned()
+        .collect()
+}
+
+fn keyed<'a: 'k, 'k>(
+    table: &'a CsvTable,
+    key: &'k [String],
+) -> impl Iterator<Item = (Vec<String>, &'a Value)> + 'k {
+    table
+        .rows
+        .iter()
+        .map(move |row| (key.iter().map(|k| cell_text(&row[k])).collect(), row))
+}
+
+fn index_rows<'a>(
+    input: &str,
+    table: &'a CsvTable,
+    key: &[String],
+) -> Result<HashMap<Vec<String>, &'a Value>> {
+    for column in key {
+        if !table.headers.contains(column) {
+            bail!("{}: key column {:?} not found", input, column);
+        }
+    }
+    let mut rows = HashMap::with_capacity(table.rows.len());
+    for (i, (row_key, row)) in keyed(table, key).enumerate() {
+        if rows.insert(row_key.clone(), row).is_some() {
+            bail!("{}: row {}: duplicate key {:?}", input, i + 1, row_key);
+        }
+    }
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_process_csv_diff() -> Result<()> {
+        let (old, new) = ("fixtures/roster_old.csv", "fixtures/roster_new.csv");
+        let diff = process_csv_diff(old, new, &DialectOptions::default(), &["Name".into()])?;
+        assert_eq!(diff.columns_added, ["Age"]);
+        assert_eq!(
+            diff.removed,
+            [json!({"Name": "Perin", "Position": "Goalkeeper", "Kit": "37"})]
+        );
+        assert_eq!(diff.added.len(), 1);
+        assert_eq!(diff.changed.len(), 2);
+        assert_eq!(
+            diff.changed[1].cells,
+            [CellChange {
+                column: "Kit".into(),
+                old: "21".into(),
+                new: "10".into()
+            }]
+        );
+        let ops: Vec<String> = diff
+            .patch_rows()
+            .iter()
+            .map(|row| cell_text(&row["_op"]))
+            .collect();
+        assert_eq!(ops, ["add", "update", "update", "delete"]);
+
+        let err = process_csv_diff(old, new, &DialectOptions::default(), &["Position".into()])
+            .unwrap_err();
+        assert!(err.to_string().contains("duplicate key [\"Goalkeeper\"]"));
+        Ok(())
+    }
+}
@@ -0,0 +1,339 @@
+use crate::utils::get_file_content;
+use anyhow::{anyhow, bail, Result};
+use chrono::{Duration, NaiveDate};
+use fake::{
+    faker::{
+        internet::en::FreeEmail,
+        name::en::{FirstName, LastName, Name},
+    },
+    Fake,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use regex_syntax::{
+    hir::{Class, ClassUnicode, ClassUnicodeRange, Group, Hir, HirKind, Repetition},
+    Parser,
+};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+/// A fake data spec, one generator per column in output order, e.g.
+///
+/// ```yaml
+/// columns:
+///   Name: { type: name }
+///   Position: { type: enum, values: [Goalkeeper, Centre-Back, Centre-Forward] }
+///   DOB: { type: date, from: 1978-01-01, to: 2004-12-31, format: "%b %d, %Y" }
+///   Kit Number: { type: int, min: 1, max: 99 }
+///   Email: { type: email }
+///   Id: { type: uuid }
+///   Code: { type: regex, pattern: '[A-Z]{3}-\d{4}' }
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FakeSpecFile {
+    columns: Map<String, Value>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum ColumnSpec {
+    Name,
+    FirstName,
+    LastName,
+    Email,
+    Uuid,
+    Int {
+        min: i64,
+        max: i64,
+    },
+    Float {
+        min: f64,
+        max: f64,
+        /// digits after the decimal point
+        #[serde(default = "default_decimals")]
+        decimals: usize,
+    },
+    Date {
+        from: String,
+        to: String,
+        /// strftime format, iso dates when omitted
+        format: Option<String>,
+    },
+    Enum {
+        values: Vec<Value>,
+    },
+    Regex {
+        pattern: String,
+    },
+}
+
+fn default_decimals() -> usize {
+    2
+}
+
+#[derive(Debug)]
+enum Generator {
+    Name,
+    FirstName,
+    LastName,
+    Email,
+    Uuid,
+    Int(i64, i64),
+    Float(f64, f64, usize),
+    Date {
+        from: NaiveDate,
+        days: i64,
+        format: String,
+    },
+    Enum(Vec<Value>),
+    Regex(Box<rand_regex::Regex>),
+}
+
+#[derive(Debug)]
+pub struct FakeSchema {
+    columns: Vec<(String, Generator)>,
+}
+
+impl FakeSchema {
+    /// Load a yaml/json fake data spec.
+    pub fn load(path: &str) -> Result<Self> {
+        let content = get_file_content(path)?;
+        let spec: FakeSpecFile = serde_yaml::from_slice(&content)?;
+        if spec.columns.is_empty() {
+            bail!("{}: no columns to generate", path);
+        }
+        let columns = spec
+            .columns
+            .into_iter()
+            .map(|(column, spec)| {
+                let generator = serde_json::from_value(spec)
+                    .map_err(Into::into)
+                    .and_then(Generator::new)
+                    .map_err(|e| anyhow!("column {:?}: {}", column, e))?;
+                Ok((column, generator))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { columns })
+    }
+
+    /// Column names in output order.
+    pub fn columns(&self) -> Vec<String> {
+        self.columns
+            .iter()
+            .map(|(column, _)| column.clone())
+            .collect()
+    }
+
+    /// Generate `count` rows, the same seed always gives the same rows.
+    pub fn rows(&self, count: usize, seed: Option<u64>) -> Vec<Value> {
+        let mut rng = match seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        (0..count)
+            .map(|_| {
+                let row = self
+                    .columns
+                    .iter()
+                    .map(|(column, generator)| (column.clone(), generator.generate(&mut rng)))
+                    .collect();
+                Value::Object(row)
+            })
+            .collect()
+    }
+}
+
+impl Generator {
+    fn new(spec: ColumnSpec) -> Result<Self> {
+        Ok(match spec {
+            ColumnSpec::Name => Generator::Name,
+            ColumnSpec::FirstName => Generator::FirstName,
+            ColumnSpec::LastName => Generator::LastName,
+            ColumnSpec::Email => Generator::Email,
+            ColumnSpec::Uuid => Generator::Uuid,
+            ColumnSpec::Int { min, max } if min > max => {
+                bail!("min {} is greater than max {}", min, max)
+            }
+            ColumnSpec::Int { min, max } => Generator::Int(min, max),
+            ColumnSpec::Float { min, max, .. } if !(max - min).is_finite() => {
+                bail!(
+                    "min {} and max {} must be finite and not that far apart",
+                    min,
+                    max
+                )
+            }
+            ColumnSpec::Float { min, max, .. } if min > max => {
+                bail!("min {} is greater than max {}", min, max)
+            }
+            ColumnSpec::Float { min, max, decimals } => Generator::Float(min, max, decimals),
+            ColumnSpec::Date { from, to, format } => {
+                let (from, to) = (parse_date(&from)?, parse_date(&to)?);
+                if from > to {
+                    bail!("date range starts after it ends");
+                }
+                Generator::Date {
+                    from,
+                    days: (to - from).num_days(),
+                    format: format.unwrap_or_else(|| "%Y-%m-%d".into()),
+                }
+            }
+            ColumnSpec::Enum { values } if values.is_empty() => bail!("enum has no values"),
+            ColumnSpec::Enum { values } => Generator::Enum(values),
+            ColumnSpec::Regex { pattern } => {
+                // anchors are implied, so a validation pattern can be reused as is
+                let bare = pattern.strip_prefix('^').unwrap_or(&pattern);
+                let bare = match bare.strip_suffix('$') {
+                    Some(rest) if !escaped_at_end(rest) => rest,
+                    _ => bare,
+                };
+                let hir = Parser::new()
+                    .parse(bare)
+                    .map_err(|e| anyhow!("bad pattern {:?}: {}", pattern, e))?;
+                // unbounded repetitions like `+` and `*` produce at most 8 extra characters
+                let regex = rand_regex::Regex::with_hir(printable(hir), 8)
+                    .map_err(|e| anyhow!("bad pattern {:?}: {}", pattern, e))?;
+                Generator::Regex(Box::new(regex))
+            }
+        })
+    }
+
+    fn generate(&self, rng: &mut StdRng) -> Value {
+        match self {
+            Generator::Name => Name().fake_with_rng::<String, _>(rng).into(),
+            Generator::FirstName => FirstName().fake_with_rng::<String, _>(rng).into(),
+            Generator::LastName => LastName().fake_with_rng::<String, _>(rng).into(),
+            Generator::Email => FreeEmail().fake_with_rng::<String, _>(rng).into(),
+            Generator::Uuid => uuid_v4(rng.gen()).into(),
+            Generator::Int(min, max) => rng.gen_range(*min..=*max).into(),
+            Generator::Float(min, max, decimals) => {
+                let value: f64 = rng.gen_range(*min..=*max);
+                let scale = 10f64.powi(*decimals as i32);
+                ((value * scale).round() / scale).into()
+            }
+            Generator::Date { from, days, format } => {
+                let date = *from + Duration::days(rng.gen_range(0..=*days));
+                date.format(format).to_string().into()
+            }
+            Generator::Enum(values) => values[rng.gen_range(0..values.len())].clone(),
+            Generator::Regex(regex) => rng.sample::<String, _>(regex.as_ref()).into(),
+        }
+    }
+}
+
+/// Narrow classes like `.` and `\d` to printable ascii, unless that leaves nothing of them.
+fn printable(hir: Hir) -> Hir {
+    match hir.into_kind() {
+        HirKind::Class(Class::Unicode(class)) => {
+            let mut ascii = ClassUnicode::new([ClassUnicodeRange::new(' ', '~')]);
+            ascii.intersect(&class);
+            let class = if ascii.ranges().is_empty() {
+                class
+            } else {
+                ascii
+            };
+            Hir::class(Class::Unicode(class))
+        }
+        HirKind::Repetition(rep) => Hir::repetition(Repetition {
+            hir: Box::new(printable(*rep.hir)),
+            ..rep
+        }),
+        HirKind::Group(group) => Hir::group(Group {
+            hir: Box::new(printable(*group.hir)),
+            ..group
+        }),
+        HirKind::Concat(items) => Hir::concat(items.into_iter().map(printable).collect()),
+        HirKind::Alternation(items) => Hir::alternation(items.into_iter().map(printable).collect()),
+        HirKind::Empty => Hir::empty(),
+        HirKind::Literal(literal) => Hir::literal(literal),
+        HirKind::Class(class) => Hir::class(class),
+        HirKind::Anchor(anchor) => Hir::anchor(anchor),
+        HirKind::WordBoundary(boundary) => Hir::word_boundary(boundary),
+    }
+}
+
+/// Whether the character after `pattern` would be escaped by a trailing backslash.
+fn escaped_at_end(pattern: &str) -> bool {
+    pattern.bytes().rev().take_while(|&b| b == b'\\').count() % 2 == 1
+}
+
+fn parse_date(date: &str) -> Result<NaiveDate> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|e| anyhow!("bad date {:?}, expected yyyy-mm-dd: {}", date, e))
+}
+
+/// Format random bytes as a version 4 uuid.
+fn uuid_v4(mut bytes: [u8; 16]) -> String {
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fake_rows() -> Result<()> {
+        let schema = FakeSchema::load("fixtures/players.fake.yaml")?;
+        let rows = schema.rows(50, Some(42));
+        assert_eq!(rows.len(), 50);
+        assert_eq!(rows, schema.rows(50, Some(42)));
+        assert_ne!(rows, schema.rows(50, Some(7)));
+
+        let row = rows[0].as_object().unwrap();
+        let columns: Vec<&str> = row.keys().map(String::as_str).collect();
+        assert_eq!(columns[..4], ["Name", "Position", "DOB", "Kit Number"]);
+        let code = regex::Regex::new(r"^[A-Z]{3}-\d{4}$")?;
+        for row in &rows {
+            let kit = row["Kit Number"].as_i64().unwrap();
+            assert!((1..=40).contains(&kit));
+            assert!(code.is_match(row["Code"].as_str().unwrap()));
+            assert!(row["Email"].as_str().unwrap().contains('@'));
+            assert_eq!(row["Id"].as_str().unwrap().len(), 36);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_bad_specs() {
+        let spec =
+            |column: &str| -> Result<Generator> { Generator::new(serde_yaml::from_str(column)?) };
+        assert!(spec("{ type: int, min: 5, max: 1 }").is_err());
+        assert!(spec("{ type: date, from: 2020-01-01, to: 2019-01-01 }").is_err());
+        assert!(spec("{ type: enum, values: [] }").is_err());
+        assert!(spec("{ type: regex, pattern: '(' }").is_err());
+        assert!(spec("{ type: shoe_size }").is_err());
+        assert!(spec("{ type: int, min: 1, max: 2, step: 1 }").is_err());
+        assert!(spec("{ type: float, min: -1.0e308, max: 1.0e308 }").is_err());
+        assert!(spec("{ type: float, min: 0, max: .inf }").is_err());
+        assert!(spec("{ type: float, min: .nan, max: 1 }").is_err());
+        assert_eq!(uuid_v4([0; 16]), "00000000-0000-4000-8000-000000000000");
+    }
+
+    #[test]
+    fn test_regex_anchors() -> Result<()> {
+        let mut rng = StdRng::seed_from_u64(1);
+        let generate = |pattern: &str, rng: &mut StdRng| -> Result<Value> {
+            let spec = serde_json::json!({"type": "regex", "pattern": pattern});
+            Ok(Generator::new(serde_json::from_value(spec)?)?.generate(rng))
+        };
+        assert_eq!(generate(r"^\d{3}\$$", &mut rng)?.as_str().unwrap().len(), 4);
+        assert!(generate(r"^[0-9]+\$", &mut rng)?
+            .as_str()
+            .unwrap()
+            .ends_with('$'));
+        assert!(generate(r"[a-z]{2}\\$", &mut rng)?
+            .as_str()
+            .unwrap()
+            .ends_with('\\'));
+        Ok(())
+    }
+}
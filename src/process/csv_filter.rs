@@ -0,0 +1,399 @@
+use anyhow::{anyhow, bail, Result};
+use regex::Regex;
+use serde_json::{Map, Value};
+use std::{cmp::Ordering, fmt, str::FromStr};
+
+/// A `--where` condition, e.g. `Position == "Goalkeeper" and `Kit Number` < 10`.
+///
+/// Columns are bare words or wrapped in backticks, strings use single or double quotes.
+/// Comparisons are numeric when both sides look like numbers and textual otherwise.
+#[derive(Debug, Clone)]
+pub enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Compare(Operand, CmpOp, Operand),
+    Matches(Operand, Regex, bool),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operand {
+    Column(String),
+    Literal(Value),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Column(String),
+    Literal(Value),
+    Cmp(CmpOp),
+    Match(bool),
+    And,
+    Or,
+    Not,
+    LParen,
+    RParen,
+}
+
+impl Expr {
+    pub fn eval(&self, row: &Value) -> bool {
+        match self {
+            Expr::And(a, b) => a.eval(row) && b.eval(row),
+            Expr::Or(a, b) => a.eval(row) || b.eval(row),
+            Expr::Not(e) => !e.eval(row),
+            Expr::Compare(a, op, b) => compare(a.resolve(row), *op, b.resolve(row)),
+            Expr::Matches(a, re, negate) => re.is_match(&cell_text(a.resolve(row))) != *negate,
+        }
+    }
+
+    /// All column names the expression refers to.
+    pub fn columns(&self) -> Vec<&str> {
+        let mut columns = Vec::new();
+        self.collect_columns(&mut columns);
+        columns
+    }
+
+    fn collect_columns<'a>(&'a self, columns: &mut Vec<&'a str>) {
+        let mut push = |operand: &'a Operand| {
+            if let Operand::Column(name) = operand {
+                columns.push(name.as_str());
+            }
+        };
+        match self {
+            Expr::And(a, b) | Expr::Or(a, b) => {
+                a.collect_columns(columns);
+                b.collect_columns(columns);
+            }
+            Expr::Not(e) => e.collect_columns(columns),
+            Expr::Compare(a, _, b) => {
+                push(a);
+                push(b);
+            }
+            Expr::Matches(a, _, _) => push(a),
+        }
+    }
+}
+
+impl Operand {
+    fn resolve<'a>(&'a self, row: &'a Value) -> &'a Value {
+        match self {
+            Operand::Column(name) => row.get(name).unwrap_or(&Value::Null),
+            Operand::Literal(value) => value,
+        }
+    }
+}
+
+/// The text of a cell as it would appear in a csv file.
+pub fn cell_text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        v => v.to_string(),
+    }
+}
+
+/// The numeric value of a cell, for typed numbers as well as numeric strings.
+pub fn cell_number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+pub fn compare(a: &Value, op: CmpOp, b: &Value) -> bool {
+    let ordering = match (cell_number(a), cell_number(b)) {
+        (Some(x), Some(y)) => x.partial_cmp(&y),
+        _ if a.is_null() || b.is_null() => {
+            // null only equals null or an empty cell
+            let equal = cell_text(a).is_empty() && cell_text(b).is_empty();
+            return match op {
+                CmpOp::Eq => equal,
+                CmpOp::Ne => !equal,
+                _ => false,
+            };
+        }
+        _ => Some(cell_text(a).cmp(&cell_text(b))),
+    };
+    let Some(ordering) = ordering else {
+        return op == CmpOp::Ne;
+    };
+    match op {
+        CmpOp::Eq => ordering == Ordering::Equal,
+        CmpOp::Ne => ordering != Ordering::Equal,
+        CmpOp::Lt => ordering == Ordering::Less,
+        CmpOp::Le => ordering != Ordering::Greater,
+        CmpOp::Gt => ordering == Ordering::Greater,
+        CmpOp::Ge => ordering != Ordering::Less,
+    }
+}
+
+impl FromStr for Expr {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tokens = tokenize(s)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let expr = parser.parse_or()?;
+        if let Some(token) = parser.tokens.get(parser.pos) {
+            bail!("unexpected {} in where expression", token);
+        }
+        Ok(expr)
+    }
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = s.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            '`' | '"' | '\'' => {
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '\\')) if c != '`' => match chars.next() {
+                            Some((_, escaped)) => text.push(escaped),
+                            None => bail!("unterminated string in where expression"),
+                        },
+                        Some((_, end)) if end == c => break,
+                        Some((_, ch)) => text.push(ch),
+                        None => bail!("unterminated {} in where expression", c),
+                    }
+                }
+                if c == '`' {
+                    Token::Column(text)
+                } else {
+                    Token::Literal(Value::String(text))
+                }
+            }
+            '=' | '!' | '<' | '>' | '&' | '|' => {
+                let next = chars.peek().map(|&(_, n)| n);
+                let (token, two) = match (c, next) {
+                    ('=', Some('=')) => (Token::Cmp(CmpOp::Eq), true),
+                    ('=', Some('~')) => (Token::Match(false), true),
+                    ('=', _) => (Token::Cmp(CmpOp::Eq), false),
+                    ('!', Some('=')) => (Token::Cmp(CmpOp::Ne), true),
+                    ('!', Some('~')) => (Token::Match(true), true),
+                    ('!', _) => (Token::Not, false),
+                    ('<', Some('=')) => (Token::Cmp(CmpOp::Le), true),
+                    ('<', Some('>')) => (Token::Cmp(CmpOp::Ne), true),
+                    ('<', _) => (Token::Cmp(CmpOp::Lt), false),
+                    ('>', Some('=')) => (Token::Cmp(CmpOp::Ge), true),
+                    ('>', _) => (Token::Cmp(CmpOp::Gt), false),
+                    ('&', Some('&')) => (Token::And, true),
+                    ('|', Some('|')) => (Token::Or, true),
+                    _ => bail!("unexpected {:?} in where expression", c),
+                };
+                if two {
+                    chars.next();
+                }
+                token
+            }
+            _ => {
+                let mut end = start + c.len_utf8();
+                while let Some(&(i, ch)) = chars.peek() {
+                    if ch.is_whitespace() || "()=!<>&|`\"'".contains(ch) {
+                        break;
+                    }
+                    end = i + ch.len_utf8();
+                    chars.next();
+                }
+                let word = &s[start..end];
+                match word.to_ascii_lowercase().as_str() {
+                    "and" => Token::And,
+                    "or" => Token::Or,
+                    "not" => Token::Not,
+                    "true" => Token::Literal(Value::Bool(true)),
+                    "false" => Token::Literal(Value::Bool(false)),
+                    "null" => Token::Literal(Value::Null),
+                    _ => match serde_json::from_str::<serde_json::Number>(word) {
+                        Ok(n) => Token::Literal(Value::Number(n)),
+                        Err(_) => Token::Column(word.to_string()),
+                    },
+                }
+            }
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        if self.tokens.get(self.pos) == Some(token) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Expr> {
+        let mut expr = self.parse_and()?;
+        while self.eat(&Token::Or) {
+            expr = Expr::Or(Box::new(expr), Box::new(self.parse_and()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr> {
+        let mut expr = self.parse_unary()?;
+        while self.eat(&Token::And) {
+            expr = Expr::And(Box::new(expr), Box::new(self.parse_unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr> {
+        if self.eat(&Token::Not) {
+            return Ok(Expr::Not(Box::new(self.parse_unary()?)));
+        }
+        if self.eat(&Token::LParen) {
+            let expr = self.parse_or()?;
+            if !self.eat(&Token::RParen) {
+                bail!("missing ')' in where expression");
+            }
+            return Ok(expr);
+        }
+        let left = self.parse_operand()?;
+        match self.next() {
+            Some(Token::Cmp(op)) => Ok(Expr::Compare(left, op, self.parse_operand()?)),
+            Some(Token::Match(negate)) => match self.next() {
+                Some(Token::Literal(Value::String(pattern))) => {
+                    Ok(Expr::Matches(left, Regex::new(&pattern)?, negate))
+                }
+                _ => bail!("expected a quoted regex after =~"),
+            },
+            Some(token) => bail!("expected a comparison, found {}", token),
+            None => bail!("expected a comparison at the end of the where expression"),
+        }
+    }
+
+    fn parse_operand(&mut self) -> Result<Operand> {
+        match self.next() {
+            Some(Token::Column(name)) => Ok(Operand::Column(name)),
+            Some(Token::Literal(value)) => Ok(Operand::Literal(value)),
+            Some(token) => bail!("expected a column or value, found {}", token),
+            None => Err(anyhow!("unexpected end of where expression")),
+        }
+    }
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Column(name) => write!(f, "`{}`", name),
+            Token::Literal(value) => write!(f, "{}", value),
+            Token::Cmp(op) => write!(f, "{:?}", op),
+            Token::Match(false) => write!(f, "=~"),
+            Token::Match(true) => write!(f, "!~"),
+            Token::And => write!(f, "and"),
+            Token::Or => write!(f, "or"),
+            Token::Not => write!(f, "not"),
+            Token::LParen => write!(f, "'('"),
+            Token::RParen => write!(f, "')'"),
+        }
+    }
+}
+
+/// Keep only the `select`ed columns, in that order, then apply the `old=new` renames.
+pub fn project_row(row: Value, select: &[String], rename: &[(String, String)]) -> Value {
+    let Value::Object(mut obj) = row else {
+        return row;
+    };
+    if !select.is_empty() {
+        obj = select
+            .iter()
+            .filter_map(|name| obj.remove(name).map(|v| (name.clone(), v)))
+            .collect();
+    }
+    if !rename.is_empty() {
+        obj = obj
+            .into_iter()
+            .map(|(key, value)| {
+                let key = rename
+                    .iter()
+                    .find(|(old, _)| *old == key)
+                    .map_or(key, |(_, new)| new.clone());
+                (key, value)
+            })
+            .collect::<Map<_, _>>();
+    }
+    Value::Object(obj)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn buffon() -> Value {
+        json!({"Name": "Gianluigi Buffon", "Position": "Goalkeeper", "Kit Number": "77"})
+    }
+
+    fn check(expr: &str) -> bool {
+        expr.parse::<Expr>().unwrap().eval(&buffon())
+    }
+
+    #[test]
+    fn test_where_comparisons() {
+        assert!(check(r#"Position == "Goalkeeper""#));
+        assert!(check(r#"Position != 'Forward'"#));
+        assert!(check("`Kit Number` > 10"));
+        assert!(!check("`Kit Number` < 9"));
+        assert!(check("Missing == null"));
+    }
+
+    #[test]
+    fn test_where_logic_and_regex() {
+        assert!(check(r#"Position == "Goalkeeper" and `Kit Number` >= 77"#));
+        assert!(check(r#"Position == "Forward" or Name =~ "^Gianluigi""#));
+        assert!(check(r#"not (Name !~ "Buffon$")"#));
+        assert!(!check(
+            r#"Position == "Goalkeeper" && !(`Kit Number` == 77)"#
+        ));
+    }
+
+    #[test]
+    fn test_where_errors() {
+        assert!("Position ==".parse::<Expr>().is_err());
+        assert!("Position".parse::<Expr>().is_err());
+        assert!(r#"Name =~ "[""#.parse::<Expr>().is_err());
+        assert!("(a == 1".parse::<Expr>().is_err());
+        let expr: Expr = "a == 1 or `b c` < d".parse().unwrap();
+        assert_eq!(expr.columns(), ["a", "b c", "d"]);
+    }
+
+    #[test]
+    fn test_project_row() {
+        let row = project_row(
+            buffon(),
+            &["Kit Number".into(), "Name".into()],
+            &[("Kit Number".into(), "kit".into())],
+        );
+        assert_eq!(row, json!({"kit": "77", "Name": "Gianluigi Buffon"}));
+    }
+}
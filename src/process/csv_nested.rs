@@ -0,0 +1,182 @@
+use anyhow::{anyhow, bail, Result};
+use serde_json::{Map, Value};
+
+/// Highest array index a column may name, arrays are filled up to it with nulls.
+const MAX_INDEX: usize = 9_999;
+
+#[derive(Debug, PartialEq)]
+enum Segment<'a> {
+    Key(&'a str),
+    Index(usize),
+}
+
+/// Split a column name like `address.city` or `tags[0].name` into its path segments.
+fn parse_path(column: &str) -> Result<Vec<Segment<'_>>> {
+    let mut segments = Vec::new();
+    for part in column.split('.') {
+        let (key, mut rest) = match part.find('[') {
+            Some(pos) => part.split_at(pos),
+            None => (part, ""),
+        };
+        if key.is_empty() && (segments.is_empty() || rest.is_empty()) {
+            bail!("column {:?}: empty path segment", column);
+        }
+        if !key.is_empty() {
+            segments.push(Segment::Key(key));
+        }
+        while !rest.is_empty() {
+            let end = rest
+                .find(']')
+                .filter(|_| rest.starts_with('['))
+                .ok_or_else(|| anyhow!("column {:?}: malformed index", column))?;
+            let index: usize = rest[1..end]
+                .parse()
+                .map_err(|_| anyhow!("column {:?}: {:?} is not an index", column, &rest[1..end]))?;
+            if index > MAX_INDEX {
+                bail!(
+                    "column {:?}: index {} is over the limit of {}",
+                    column,
+                    index,
+                    MAX_INDEX
+                );
+            }
+            segments.push(Segment::Index(index));
+            rest = &rest[end + 1..];
+        }
+    }
+    Ok(segments)
+}
+
+/// Turn a flat row with dotted/bracketed keys into nested objects and arrays.
+pub fn unflatten_row(row: Value) -> Result<Value> {
+    let Value::Object(flat) = row else {
+        return Ok(row);
+    };
+    let mut root = Value::Object(Map::new());
+    for (column, value) in flat {
+        let path = parse_path(&column)?;
+        let mut node = &mut root;
+        for (i, segment) in path.iter().enumerate() {
+            let last = i + 1 == path.len();
+            let conflict = || anyhow!("column {:?} conflicts with another column", column);
+            node = match segment {
+                Segment::Key(key) => {
+                    if node.is_null() {
+                        *node = Value::Object(Map::new());
+                    }
+                    let obj = node.as_object_mut().ok_or_else(conflict)?;
+                    obj.entry(key.to_string()).or_insert(Value::Null)
+                }
+                Segment::Index(index) => {
+                    if node.is_null() {
+                        *node = Value::Array(Vec::new());
+                    }
+                    let items = node.as_array_mut().ok_or_else(conflict)?;
+                    if items.len() <= *index {
+                        items.resize(index + 1, Value::Null);
+                    }
+                    &mut items[*index]
+                }
+            };
+            if last {
+                if !node.is_null() {
+                    return Err(conflict());
+                }
+                *node = value;
+                break;
+            }
+        }
+    }
+    Ok(root)
+}
+
+/// Flatten nested objects and arrays into dotted/bracketed keys, the reverse of [`unflatten_row`].
+pub fn flatten_row(row: Value) -> Value {
+    let Value::Object(obj) = row else {
+        return row;
+    };
+    let mut flat = Map::new();
+    for (key, value) in obj {
+        flatten_into(&mut flat, key, value);
+    }
+    Value::Object(flat)
+}
+
+fn flatten_into(flat: &mut Map<String, Value>, prefix: String, value: Value) {
+    match value {
+        Value::Object(obj) => {
+            for (key, value) in obj {
+                flatten_into(flat, format!("{}.{}", prefix, key), value);
+            }
+        }
+        Value::Array(items) => {
+            for (i, value) in items.into_iter().enumerate() {
+                flatten_into(flat, format!("{}[{}]", prefix, i), value);
+            }
+        }
+        value => {
+            flat.insert(prefix, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_path() -> Result<()> {
+        assert_eq!(
+            parse_path("tags[0].name")?,
+            [
+                Segment::Key("tags"),
+                Segment::Index(0),
+                Segment::Key("name")
+            ]
+        );
+        assert_eq!(
+            parse_path("matrix[1][2]")?,
+            [Segment::Key("matrix"), Segment::Index(1), Segment::Index(2)]
+        );
+        assert!(parse_path("tags[x]").is_err());
+        assert!(parse_path("a..b").is_err());
+        let err = parse_path("tags[18446744073709551615]").unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("\"tags[18446744073709551615]\": index"));
+        assert!(parse_path("tags[4000000000]").is_err());
+        assert!(parse_path("tags[9999]").is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn test_unflatten_and_flatten_round_trip() -> Result<()> {
+        let flat = json!({
+            "Name": "Buffon",
+            "address.city": "Turin",
+            "address.zip": "10121",
+            "tags[0]": "keeper",
+            "tags[1]": "captain",
+            "clubs[0].name": "Parma",
+        });
+        let nested = unflatten_row(flat.clone())?;
+        assert_eq!(
+            nested,
+            json!({
+                "Name": "Buffon",
+                "address": {"city": "Turin", "zip": "10121"},
+                "tags": ["keeper", "captain"],
+                "clubs": [{"name": "Parma"}],
+            })
+        );
+        assert_eq!(flatten_row(nested), flat);
+        Ok(())
+    }
+
+    #[test]
+    fn test_unflatten_conflict() {
+        let err = unflatten_row(json!({"address": "Turin", "address.city": "Turin"})).unwrap_err();
+        assert!(err.to_string().contains("address.city"));
+    }
+}
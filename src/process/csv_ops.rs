@@ -0,0 +1,262 @@
+use crate::cli::{JoinKind, OutputFormat};
+use crate::process::csv_convert::CsvTable;
+use crate::process::csv_filter::{cell_number, cell_text};
+use crate::process::row_writer::{row_writer, WriterOptions};
+use crate::utils::get_writer;
+use anyhow::{anyhow, bail, Result};
+use serde_json::{Map, Value};
+use std::{cmp::Ordering, collections::HashMap, collections::HashSet, str::FromStr};
+
+/// One sort column, written `column[:num|:str][:desc]` on the command line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SortKey {
+    pub column: String,
+    pub numeric: bool,
+    pub descending: bool,
+}
+
+impl FromStr for SortKey {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut key = SortKey {
+            column: String::new(),
+            numeric: false,
+            descending: false,
+        };
+        // modifiers are peeled off the end so column names may still hold a ':'
+        let mut column = s;
+        while let Some((rest, modifier)) = column.rsplit_once(':') {
+            match modifier {
+                "num" => key.numeric = true,
+                "str" => key.numeric = false,
+                "desc" => key.descending = true,
+                "asc" => key.descending = false,
+                _ => break,
+            }
+            column = rest;
+        }
+        if column.is_empty() {
+            bail!("sort key {:?} has no column", s);
+        }
+        key.column = column.to_string();
+        Ok(key)
+    }
+}
+
+impl SortKey {
+    fn compare(&self, a: &Value, b: &Value) -> Ordering {
+        let (a, b) = (&a[&self.column], &b[&self.column]);
+        let ord = if self.numeric {
+            // cells that aren't numbers go last, in text order
+            match (cell_number(a), cell_number(b)) {
+                (Some(x), Some(y)) => x.total_cmp(&y),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => cell_text(a).cmp(&cell_text(b)),
+            }
+        } else {
+            cell_text(a).cmp(&cell_text(b))
+        };
+        if self.descending {
+            ord.reverse()
+        } else {
+            ord
+        }
+    }
+}
+
+/// Join two tables on the `on` columns, which must exist on both sides.
+///
+/// Right hand columns that clash with a left hand one get a `_right` suffix, and with
+/// a left join the right hand cells of unmatched rows are null.
+pub fn join_tables(
+    left: CsvTable,
+    right: CsvTable,
+    on: &[String],
+    kind: JoinKind,
+) -> Result<CsvTable> {
+    check_columns(&left.headers, on)?;
+    check_columns(&right.headers, on)?;
+    let right_columns: Vec<(&String, String)> = right
+        .headers
+        .iter()
+        .filter(|h| !on.contains(h))
+        .map(|h| {
+            let name = if left.headers.contains(h) {
+                format!("{}_right", h)
+            } else {
+                h.clone()
+            };
+            (h, name)
+        })
+        .collect();
+
+    let mut index: HashMap<Vec<String>, Vec<&Value>> = HashMap::new();
+    for row in &right.rows {
+        index.entry(row_key(row, on)).or_default().push(row);
+    }
+    let mut rows = Vec::with_capacity(left.rows.len());
+    for row in &left.rows {
+        let matches = index.get(&row_key(row, on));
+        let extend = |right: Option<&Value>| {
+            let mut joined: Map<String, Value> = row.as_object().cloned().unwrap_or_default();
+            for (column, name) in &right_columns {
+                let cell = right.map_or(Value::Null, |r| r[column.as_str()].clone());
+                joined.insert(name.clone(), cell);
+            }
+            Value::Object(joined)
+        };
+        match (matches, kind) {
+            (Some(matches), _) => rows.extend(matches.iter().map(|&r| extend(Some(r)))),
+            (None, JoinKind::Left) => rows.push(extend(None)),
+            (None, JoinKind::Inner) => {}
+        }
+    }
+    let mut headers = left.headers;
+    headers.extend(right_columns.into_iter().map(|(_, name)| name));
+    Ok(CsvTable { headers, rows })
+}
+
+/// Stable sort on several keys, earlier keys take precedence.
+pub fn sort_rows(rows: &mut [Value], headers: &[String], keys: &[SortKey]) -> Result<()> {
+    let columns: Vec<String> = keys.iter().map(|k| k.column.clone()).collect();
+    check_columns(headers, &columns)?;
+    rows.sort_by(|a, b| {
+        keys.iter()
+            .map(|key| key.compare(a, b))
+            .find(|ord| ord.is_ne())
+            .unwrap_or(Ordering::Equal)
+    });
+    Ok(())
+}
+
+/// Keep the first row of each distinct combination of `columns`, or of whole rows when empty.
+pub fn dedup_rows(rows: Vec<Value>, headers: &[String], columns: &[String]) -> Result<Vec<Value>> {
+    check_columns(headers, columns)?;
+    let columns = if columns.is_empty() { headers } else { columns };
+    let mut seen = HashSet::new();
+    Ok(rows
+        .into_iter()
+        .filter(|row| seen.insert(row_key(row, columns)))
+        .collect())
+}
+
+/// Write rows to `output` in any output format, csv output keeps `headers` even without rows.
+pub fn write_rows(
+    output: &str,
+    format: OutputFormat,
+    headers: &[String],
+    rows: &[Value],
+) -> Result<()> {
+    let opts = WriterOptions {
+        headers: headers.to_vec(),
+        ..Default::default()
+    };
+    let mut writer = row_writer(format, get_writer(output)?, &opts);
+    for row in rows {
+        writer.write_row(row)?;
+    }
+    writer.finish()
+}
+
+fn row_key(row: &Value, columns: &[String]) -> Vec<String> {
+    columns.iter().map(|c| cell_text(&row[c])).collect()
+}
+
+fn check_columns(headers: &[String], columns: &[String]) -> Result<()> {
+    match columns.iter().find(|c| !headers.contains(c)) {
+        Some(column) => Err(anyhow!(
+            "unknown column {:?}, expected one of {:?}",
+            column,
+            headers
+        )),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn table(headers: &[&str], rows: Vec<Value>) -> CsvTable {
+        CsvTable {
+            headers: headers.iter().map(|h| h.to_string()).collect(),
+            rows,
+        }
+    }
+
+    #[test]
+    fn test_join_tables() -> Result<()> {
+        let players = table(
+            &["Name", "Club"],
+            vec![
+                json!({"Name": "Buffon", "Club": "Juventus"}),
+                json!({"Name": "Dybala", "Club": "Roma"}),
+            ],
+        );
+        let caps = || {
+            table(
+                &["Name", "Club", "Caps"],
+                vec![json!({"Name": "Buffon", "Club": "Italy", "Caps": "176"})],
+            )
+        };
+        let on = ["Name".to_string()];
+        let inner = join_tables(players, caps(), &on, JoinKind::Inner)?;
+        assert_eq!(inner.headers, ["Name", "Club", "Club_right", "Caps"]);
+        assert_eq!(
+            inner.rows,
+            [json!({"Name": "Buffon", "Club": "Juventus", "Club_right": "Italy", "Caps": "176"})]
+        );
+
+        let players = table(
+            &["Name"],
+            vec![json!({"Name": "Buffon"}), json!({"Name": "Dybala"})],
+        );
+        let left = join_tables(players, caps(), &on, JoinKind::Left)?;
+        assert_eq!(
+            left.rows[1],
+            json!({"Name": "Dybala", "Club": null, "Caps": null})
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_sort_rows() -> Result<()> {
+        let headers = ["Name".to_string(), "Kit".to_string()];
+        let mut rows = vec![
+            json!({"Name": "Buffon", "Kit": "77"}),
+            json!({"Name": "Perin", "Kit": "1"}),
+            json!({"Name": "Chiesa", "Kit": "7"}),
+            json!({"Name": "Alex", "Kit": "7"}),
+        ];
+        let keys = ["Kit:num:desc".parse()?, "Name".parse()?];
+        sort_rows(&mut rows, &headers, &keys)?;
+        let names: Vec<String> = rows.iter().map(|r| cell_text(&r["Name"])).collect();
+        assert_eq!(names, ["Buffon", "Alex", "Chiesa", "Perin"]);
+
+        sort_rows(&mut rows, &headers, &["Kit".parse()?])?;
+        assert_eq!(cell_text(&rows[1]["Kit"]), "7");
+        assert_eq!(cell_text(&rows[3]["Kit"]), "77");
+        Ok(())
+    }
+
+    #[test]
+    fn test_dedup_rows() -> Result<()> {
+        let headers = ["Name".to_string(), "Position".to_string()];
+        let rows = vec![
+            json!({"Name": "Buffon", "Position": "Goalkeeper"}),
+            json!({"Name": "Perin", "Position": "Goalkeeper"}),
+            json!({"Name": "Buffon", "Position": "Goalkeeper"}),
+        ];
+        assert_eq!(dedup_rows(rows.clone(), &headers, &[])?.len(), 2);
+        let by_position = dedup_rows(rows, &headers, &["Position".into()])?;
+        assert_eq!(
+            by_position,
+            [json!({"Name": "Buffon", "Position": "Goalkeeper"})]
+        );
+        assert!(dedup_rows(vec![], &headers, &["Kit".into()]).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,194 @@
+use crate::cli::InferMode;
+use crate::process::csv_convert::{align_row, record_to_row, transform, ConvertOptions, CsvSource};
+use crate::process::csv_rejects::{BadRow, Rejects};
+use crate::process::csv_types::{merge_scans, scan_types, ColumnTypes, RowTyper};
+use crate::process::row_writer::{encode_row, RowWriter};
+use anyhow::Result;
+use csv::{Position, StringRecord};
+use rayon::prelude::*;
+use rayon::ThreadPoolBuilder;
+use serde_json::Value;
+
+/// Records are read on the calling thread and handed to the pool this many at a time.
+const CHUNK_SIZE: usize = 4096;
+
+/// Records read from one input, still to be converted.
+struct Chunk<'a, 'h> {
+    input: &'a str,
+    headers: &'h [String],
+    records: Vec<csv::Result<StringRecord>>,
+}
+
+/// Rows of a chunk, keyed by the merged headers but not typed yet.
+struct ParsedChunk<'a> {
+    input: &'a str,
+    rows: Vec<Parsed>,
+}
+
+enum Parsed {
+    Row(Position, Value),
+    Bad(BadRow),
+}
+
+enum Converted {
+    Row(Value),
+    /// already serialized for the output format
+    Encoded(String),
+    Bad(BadRow),
+    Dropped,
+}
+
+/// Convert csv rows on a thread pool, chunk by chunk, writing them out in input order.
+///
+/// Parsing, type inference, `--where`/`--select` and json/yaml serialization happen on the
+/// pool; only reading records and writing the output stay on the calling thread.
+pub fn convert_parallel(
+    sources: Vec<CsvSource>,
+    headers: &[String],
+    opts: &ConvertOptions,
+    writer: &mut dyn RowWriter,
+    rejects: &mut Rejects,
+) -> Result<()> {
+    let pool = ThreadPoolBuilder::new().num_threads(opts.jobs).build()?;
+    // a couple of chunks per thread keeps every thread busy while the batch is read
+    let batch_size = pool.current_num_threads() * 2;
+    let column_wide = opts.infer == InferMode::Column;
+    let typer = RowTyper::new(headers, opts.infer, &opts.types, &[])?;
+    // column-wide inference has to see every row before the first one is written
+    let mut pending = Vec::new();
+    for (input, reader, file_headers) in sources {
+        let mut records = reader.into_records();
+        loop {
+            let batch: Vec<Chunk> = (0..batch_size)
+                .map_while(|_| {
+                    let records: Vec<_> = records.by_ref().take(CHUNK_SIZE).collect();
+                    (!records.is_empty()).then_some(Chunk {
+                        input,
+                        headers: &file_headers,
+                        records,
+                    })
+                })
+                .collect();
+            if batch.is_empty() {
+                break;
+            }
+            if column_wide {
+                let parsed: Result<Vec<_>> =
+                    pool.install(|| batch.into_par_iter().map(|c| c.parse(headers)).collect());
+                pending.extend(parsed?);
+            } else {
+                let converted: Vec<_> = pool.install(|| {
+                    batch
+                        .into_par_iter()
+                        .map(|c| Ok(c.parse(headers)?.convert(headers, &typer, opts)))
+                        .collect()
+                });
+                write_converted(converted, writer, rejects)?;
+            }
+        }
+    }
+    if column_wide {
+        let scanned = pool.install(|| {
+            pending
+                .par_iter()
+                .map(ParsedChunk::scan)
+                .reduce(ColumnTypes::new, merge_scans)
+        });
+        let typer = RowTyper::with_scanned(headers, opts.infer, &opts.types, scanned)?;
+        let converted: Vec<_> = pool.install(|| {
+            pending
+                .into_par_iter()
+                .map(|c| Ok(c.convert(headers, &typer, opts)))
+                .collect()
+        });
+        write_converted(converted, writer, rejects)?;
+    }
+    Ok(())
+}
+
+fn write_converted(
+    chunks: Vec<Result<Vec<Result<Converted>>>>,
+    writer: &mut dyn RowWriter,
+    rejects: &mut Rejects,
+) -> Result<()> {
+    for chunk in chunks {
+        for row in chunk? {
+            match row? {
+                Converted::Row(row) => writer.write_row(&row)?,
+                Converted::Encoded(encoded) => writer.write_encoded(&encoded)?,
+                Converted::Bad(bad) => rejects.reject(bad)?,
+                Converted::Dropped => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+impl<'a> Chunk<'a, '_> {
+    fn parse(self, headers: &[String]) -> Result<ParsedChunk<'a>> {
+        let aligned = self.headers == headers;
+        let rows = self
+            .records
+            .into_iter()
+            .map(|record| match record {
+                Ok(record) => {
+                    let pos = record.position().cloned().unwrap_or_else(Position::new);
+                    let row = record_to_row(self.headers, &record);
+                    let row = if aligned {
+                        row
+                    } else {
+                        align_row(row, headers)
+                    };
+                    Ok(Parsed::Row(pos, row))
+                }
+                Err(e) => Ok(Parsed::Bad(BadRow::from_csv_error(
+                    self.input,
+                    e,
+                    self.headers,
+                )?)),
+            })
+            .collect::<Result<_>>()?;
+        Ok(ParsedChunk {
+            input: self.input,
+            rows,
+        })
+    }
+}
+
+impl ParsedChunk<'_> {
+    fn scan(&self) -> ColumnTypes {
+        scan_types(self.rows.iter().filter_map(|row| match row {
+            Parsed::Row(_, row) => Some(row),
+            Parsed::Bad(_) => None,
+        }))
+    }
+
+    fn convert(
+        self,
+        headers: &[String],
+        typer: &RowTyper,
+        opts: &ConvertOptions,
+    ) -> Vec<Result<Converted>> {
+        let input = self.input;
+        self.rows
+            .into_iter()
+            .map(|row| {
+                let (pos, mut row) = match row {
+                    Parsed::Row(pos, row) => (pos, row),
+                    Parsed::Bad(bad) => return Ok(Converted::Bad(bad)),
+                };
+                if let Err(e) = typer.apply(&mut row) {
+                    let bad = BadRow::at_cell(input, &pos, headers, &e.column, e.reason);
+                    return Ok(Converted::Bad(bad));
+                }
+                let Some(row) = transform(opts.tag_source(row, input), opts)? else {
+                    return Ok(Converted::Dropped);
+                };
+                match encode_row(opts.format, &opts.writer, &row) {
+                    Some(encoded) => Ok(Converted::Encoded(encoded?)),
+                    None => Ok(Converted::Row(row)),
+                }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,958 @@
+use crate::process::csv_convert::{read_csv_table, CsvTable};
+use crate::process::csv_filter::{cell_number, cell_text, compare, CmpOp};
+use crate::process::csv_sniff::DialectOptions;
+use anyhow::{anyhow, bail, Result};
+use regex::Regex;
+use serde_json::{Map, Value};
+use sqlparser::{
+    ast::{
+        self, BinaryOperator, DuplicateTreatment, FunctionArg, FunctionArgExpr, FunctionArguments,
+        GroupByExpr, JoinConstraint, JoinOperator, SelectItem, SetExpr, Statement, TableFactor,
+        UnaryOperator,
+    },
+    dialect::GenericDialect,
+    parser::Parser,
+};
+use std::{cmp::Ordering, collections::HashMap, collections::HashSet};
+
+/// A csv file under the name queries refer to it by.
+struct Source {
+    name: String,
+    headers: Vec<String>,
+    rows: Vec<Value>,
+}
+
+/// One row of every joined table, `None` for the missing side of a left join.
+type Joined<'a> = Vec<Option<&'a Value>>;
+
+/// An expression with its columns resolved against the joined tables.
+#[derive(Debug, Clone)]
+enum Scalar {
+    Column(usize, String),
+    Literal(Value),
+    Binary(Box<Scalar>, BinaryOperator, Box<Scalar>),
+    Not(Box<Scalar>),
+    Neg(Box<Scalar>),
+    IsNull(Box<Scalar>, bool),
+    InList(Box<Scalar>, Vec<Scalar>, bool),
+    Between(Box<Scalar>, Box<Scalar>, Box<Scalar>, bool),
+    Like(Box<Scalar>, Regex, bool),
+    Call(String, Vec<Scalar>),
+    /// index of an aggregate computed over the rows of a group
+    Aggregate(usize),
+}
+
+#[derive(Debug)]
+struct Aggregate {
+    func: String,
+    /// `None` for `count(*)`
+    arg: Option<Scalar>,
+    distinct: bool,
+}
+
+enum OrderKey {
+    /// a column of the output, by name or position
+    Output(usize),
+    Expr(Scalar),
+}
+
+/// Resolves columns and collects the aggregates an expression uses.
+struct Compiler<'s> {
+    sources: &'s [Source],
+    /// tables that can be referred to, later ones aren't joined in yet
+    visible: usize,
+    aggregates: Vec<Aggregate>,
+}
+
+/// Run a `SELECT` over csv files, `tables` maps table names to files.
+///
+/// Supported are WHERE, JOIN/LEFT JOIN ... ON, GROUP BY with count, sum, avg, min and max,
+/// HAVING, DISTINCT, ORDER BY, LIMIT and OFFSET. Cells compare as numbers when both sides
+/// look like numbers and as text otherwise, just like `--where`.
+pub fn process_csv_query(
+    sql: &str,
+    tables: &[(String, String)],
+    dialect: &DialectOptions,
+) -> Result<CsvTable> {
+    let query = parse_query(sql)?;
+    let SetExpr::Select(select) = *query.body else {
+        bail!("only a single SELECT is supported");
+    };
+    if query.with.is_some() || query.fetch.is_some() || select.into.is_some() {
+        bail!("WITH, FETCH and INTO aren't supported");
+    }
+    let from = match select.from.as_slice() {
+        [from] => from,
+        [] => bail!("a query needs a FROM table"),
+        _ => bail!("list one table in FROM and add others with JOIN"),
+    };
+
+    let mut relations = vec![&from.relation];
+    relations.extend(from.joins.iter().map(|join| &join.relation));
+    let mut sources = Vec::with_capacity(relations.len());
+    for relation in relations {
+        let source = load_source(relation, tables, dialect)?;
+        if sources.iter().any(|s: &Source| s.name == source.name) {
+            bail!("table {:?} is used twice, give it an alias", source.name);
+        }
+        sources.push(source);
+    }
+
+    let mut compiler = Compiler {
+        sources: &sources,
+        visible: 1,
+        aggregates: Vec::new(),
+    };
+    let mut joined: Vec<Joined> = sources[0].rows.iter().map(|r| vec![Some(r)]).collect();
+    for (i, join) in from.joins.iter().enumerate() {
+        compiler.visible = i + 2;
+        let (constraint, left) = match &join.join_operator {
+            JoinOperator::Inner(constraint) => (constraint, false),
+            JoinOperator::LeftOuter(constraint) => (constraint, true),
+            op => bail!("unsupported join {:?}, use JOIN or LEFT JOIN", op),
+        };
+        let JoinConstraint::On(on) = constraint else {
+            bail!("joins need an ON condition");
+        };
+        let on = compiler.scalar(on, false)?;
+        joined = join_rows(joined, &sources[i + 1].rows, &on, left);
+    }
+
+    if let Some(selection) = &select.selection {
+        let selection = compiler.scalar(selection, false)?;
+        joined.retain(|row| truthy(&selection.eval(row, &[])));
+    }
+
+    let mut columns: Vec<(String, Scalar)> = Vec::new();
+    for item in &select.projection {
+        compiler.select_item(item, &mut columns)?;
+    }
+    let group_by = match &select.group_by {
+        GroupByExpr::Expressions(exprs, modifiers) if modifiers.is_empty() => exprs
+            .iter()
+            .map(|e| compiler.group_key(e, &columns))
+            .collect::<Result<Vec<_>>>()?,
+        _ => bail!("unsupported GROUP BY"),
+    };
+    let having = select
+        .having
+        .as_ref()
+        .map(|having| compiler.scalar(having, true))
+        .transpose()?;
+    let names: Vec<String> = columns.iter().map(|(name, _)| name.clone()).collect();
+    let order_by = query
+        .order_by
+        .iter()
+        .flat_map(|order| &order.exprs)
+        .map(|order| {
+            let key = compiler.order_key(&order.expr, &names)?;
+            Ok((key, order.asc == Some(false)))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let grouped = !group_by.is_empty() || !compiler.aggregates.is_empty();
+    if having.is_some() && !grouped {
+        bail!("HAVING needs GROUP BY or an aggregate");
+    }
+    if grouped {
+        // outside an aggregate a group only has one value for the columns it's grouped by
+        let mut keys = HashSet::new();
+        for key in &group_by {
+            key.visit(&mut |s| {
+                if let Scalar::Column(i, column) = s {
+                    keys.insert((*i, column.clone()));
+                }
+            });
+        }
+        let order_exprs = order_by.iter().filter_map(|(key, _)| match key {
+            OrderKey::Expr(expr) => Some(expr),
+            OrderKey::Output(_) => None,
+        });
+        let used = columns
+            .iter()
+            .map(|(_, column)| column)
+            .chain(&having)
+            .chain(order_exprs);
+        for scalar in used {
+            let mut ungrouped = None;
+            scalar.visit(&mut |s| match s {
+                Scalar::Column(i, column) if !keys.contains(&(*i, column.clone())) => {
+                    ungrouped.get_or_insert_with(|| column.clone());
+                }
+                _ => {}
+            });
+            if let Some(column) = ungrouped {
+                bail!(
+                    "column {:?} must be in GROUP BY or inside an aggregate",
+                    column
+                );
+            }
+        }
+    }
+    let groups: Vec<Vec<Joined>> = if grouped {
+        group_rows(joined, &group_by)
+    } else {
+        joined.into_iter().map(|row| vec![row]).collect()
+    };
+
+    let none = vec![None; sources.len()];
+    let mut results: Vec<(Vec<Value>, Vec<Value>)> = Vec::with_capacity(groups.len());
+    for group in &groups {
+        let aggregates: Vec<Value> = compiler
+            .aggregates
+            .iter()
+            .map(|agg| agg.eval(group))
+            .collect();
+        // plain columns of a group take the value of its first row
+        let row = group.first().unwrap_or(&none);
+        if let Some(having) = &having {
+            if !truthy(&having.eval(row, &aggregates)) {
+                continue;
+            }
+        }
+        let output: Vec<Value> = columns
+            .iter()
+            .map(|(_, column)| column.eval(row, &aggregates))
+            .collect();
+        let keys = order_by
+            .iter()
+            .map(|(key, _)| match key {
+                OrderKey::Output(i) => output[*i].clone(),
+                OrderKey::Expr(expr) => expr.eval(row, &aggregates),
+            })
+            .collect();
+        results.push((output, keys));
+    }
+
+    if select.distinct.is_some() {
+        let mut seen = HashSet::new();
+        results.retain(|(output, _)| seen.insert(output.iter().map(cell_text).collect::<Vec<_>>()));
+    }
+    results.sort_by(|(_, a), (_, b)| {
+        a.iter()
+            .zip(b)
+            .zip(&order_by)
+            .map(|((a, b), (_, descending))| {
+                let ord = order_values(a, b);
+                if *descending {
+                    ord.reverse()
+                } else {
+                    ord
+                }
+            })
+            .find(|ord| ord.is_ne())
+            .unwrap_or(Ordering::Equal)
+    });
+    let offset = match &query.offset {
+        Some(offset) => count_literal(&offset.value, "OFFSET")?,
+        None => 0,
+    };
+    let limit = match &query.limit {
+        Some(limit) => count_literal(limit, "LIMIT")?,
+        None => usize::MAX,
+    };
+
+    let rows = results
+        .into_iter()
+        .skip(offset)
+        .take(limit)
+        .map(|(output, _)| {
+            let row: Map<String, Value> = names.iter().cloned().zip(output).collect();
+            Value::Object(row)
+        })
+        .collect();
+    Ok(CsvTable {
+        headers: names,
+        rows,
+    })
+}
+
+fn parse_query(sql: &str) -> Result<ast::Query> {
+    let mut statements = Parser::parse_sql(&GenericDialect {}, sql)?;
+    match (statements.pop(), statements.is_empty()) {
+        (Some(Statement::Query(query)), true) => Ok(*query),
+        (Some(_), true) => bail!("only SELECT queries are supported"),
+        _ => bail!("expected exactly one SELECT query"),
+    }
+}
+
+fn load_source(
+    relation: &TableFactor,
+    tables: &[(String, String)],
+    dialect: &DialectOptions,
+) -> Result<Source> {
+    let TableFactor::Table { name, alias, .. } = relation else {
+        bail!("only csv tables can be queried, not {}", relation);
+    };
+    let table = ident_name(name.0.last());
+    let (_, path) = tables
+        .iter()
+        .find(|(name, _)| *name == table)
+        .ok_or_else(|| {
+            anyhow!(
+                "unknown table {:?}, add it with --table {}=<file>",
+                table,
+                table
+            )
+        })?;
+    let csv = read_csv_table(path, dialect)?;
+    Ok(Source {
+        name: alias
+            .as_ref()
+            .map_or(table, |alias| alias.name.value.clone()),
+        headers: csv.headers,
+        rows: csv.rows,
+    })
+}
+
+fn ident_name(ident: Option<&ast::Ident>) -> String {
+    ident.map(|i| i.value.clone()).unwrap_or_default()
+}
+
+/// Join each row on the left with the matching `right` rows. An equality of a left and
+/// a right column is looked up in an index, anything else is checked pair by pair.
+fn join_rows<'a>(
+    left: Vec<Joined<'a>>,
+    right: &'a [Value],
+    on: &Scalar,
+    keep: bool,
+) -> Vec<Joined<'a>> {
+    let side = left.first().map_or(0, Vec::len);
+    let equi = match on {
+        Scalar::Binary(a, BinaryOperator::Eq, b) => match (a.as_ref(), b.as_ref()) {
+            (Scalar::Column(i, l), Scalar::Column(j, r)) if *i < side && *j == side => {
+                Some((*i, l, r))
+            }
+            (Scalar::Column(j, r), Scalar::Column(i, l)) if *i < side && *j == side => {
+                Some((*i, l, r))
+            }
+            _ => None,
+        },
+        _ => None,
+    };
+    let index = equi.map(|(_, _, column)| {
+        let mut index: HashMap<String, Vec<&Value>> = HashMap::new();
+        for row in right {
+            index.entry(join_key(&row[column])).or_default().push(row);
+        }
+        index
+    });
+
+    let mut joined = Vec::with_capacity(left.len());
+    for row in left {
+        let matches: Vec<&Value> = match (&index, equi) {
+            (Some(index), Some((i, column, _))) => {
+                let key = join_key(row[i].map_or(&Value::Null, |r| &r[column]));
+                index.get(&key).cloned().unwrap_or_default()
+            }
+            _ => right
+                .iter()
+                .filter(|candidate| {
+                    let mut pair = row.clone();
+                    pair.push(Some(candidate));
+                    truthy(&on.eval(&pair, &[]))
+                })
+                .collect(),
+        };
+        if matches.is_empty() && keep {
+            let mut row = row;
+            row.push(None);
+            joined.push(row);
+            continue;
+        }
+        for matched in matches {
+            let mut pair = row.clone();
+            pair.push(Some(matched));
+            joined.push(pair);
+        }
+    }
+    joined
+}
+
+/// Numbers are keyed by value so `7` joins `7.0`, the same as comparing them does.
+fn join_key(value: &Value) -> String {
+    match cell_number(value) {
+        Some(n) => n.to_string(),
+        None => cell_text(value),
+    }
+}
+
+/// Split rows into groups by the text of the group expressions, in first-seen order.
+/// Without group expressions all rows form one group, even when there are none.
+fn group_rows<'a>(rows: Vec<Joined<'a>>, group_by: &[Scalar]) -> Vec<Vec<Joined<'a>>> {
+    if group_by.is_empty() {
+        return vec![rows];
+    }
+    let mut index: HashMap<Vec<String>, usize> = HashMap::new();
+    let mut groups: Vec<Vec<Joined>> = Vec::new();
+    for row in rows {
+        let key: Vec<String> = group_by
+            .iter()
+            .map(|expr| cell_text(&expr.eval(&row, &[])))
+            .collect();
+        let i = *index.entry(key).or_insert_with(|| {
+            groups.push(Vec::new());
+            groups.len() - 1
+        });
+        groups[i].push(row);
+    }
+    groups
+}
+
+fn count_literal(expr: &ast::Expr, clause: &str) -> Result<usize> {
+    match expr {
+        ast::Expr::Value(ast::Value::Number(n, _)) => n
+            .parse()
+            .map_err(|_| anyhow!("{} must be a whole number, not {}", clause, n)),
+        expr => bail!("{} must be a number, not {}", clause, expr),
+    }
+}
+
+impl Compiler<'_> {
+    fn select_item(
+        &mut self,
+        item: &SelectItem,
+        columns: &mut Vec<(String, Scalar)>,
+    ) -> Result<()> {
+        match item {
+            SelectItem::UnnamedExpr(expr) => {
+                let name = match expr {
+                    ast::Expr::Identifier(ident) => ident.value.clone(),
+                    ast::Expr::CompoundIdentifier(idents) => ident_name(idents.last()),
+                    expr => expr.to_string(),
+                };
+                columns.push((name, self.scalar(expr, true)?));
+            }
+            SelectItem::ExprWithAlias { expr, alias } => {
+                columns.push((alias.value.clone(), self.scalar(expr, true)?));
+            }
+            SelectItem::Wildcard(_) => {
+                for i in 0..self.sources.len() {
+                    self.push_all(i, columns);
+                }
+            }
+            SelectItem::QualifiedWildcard(name, _) => {
+                let table = ident_name(name.0.last());
+                let i = self.source(&table)?;
+                self.push_all(i, columns);
+            }
+        }
+        Ok(())
+    }
+
+    /// Every column of a table, prefixed by the table name when another table has it too.
+    fn push_all(&self, i: usize, columns: &mut Vec<(String, Scalar)>) {
+        let source = &self.sources[i];
+        for header in &source.headers {
+            let shared = self
+                .sources
+                .iter()
+                .enumerate()
+                .any(|(j, s)| j != i && s.headers.contains(header));
+            let name = match shared {
+                true => format!("{}.{}", source.name, header),
+                false => header.clone(),
+            };
+            columns.push((name, Scalar::Column(i, header.clone())));
+        }
+    }
+
+    /// A GROUP BY expression, where output columns can be named by position or alias.
+    fn group_key(&mut self, expr: &ast::Expr, columns: &[(String, Scalar)]) -> Result<Scalar> {
+        let output = match expr {
+            ast::Expr::Value(ast::Value::Number(n, _)) => match n.parse::<usize>() {
+                Ok(i) if (1..=columns.len()).contains(&i) => Some(&columns[i - 1]),
+                _ => bail!("GROUP BY {} is not a column of the result", n),
+            },
+            // a column of the tables wins over an output alias of the same name
+            ast::Expr::Identifier(ident) if self.column(&ident.value).is_err() => {
+                columns.iter().find(|(name, _)| *name == ident.value)
+            }
+            _ => None,
+        };
+        match output {
+            Some((name, scalar)) => {
+                let mut aggregate = false;
+                scalar.visit(&mut |s| aggregate |= matches!(s, Scalar::Aggregate(_)));
+                if aggregate {
+                    bail!("GROUP BY {} refers to an aggregate", name);
+                }
+                Ok(scalar.clone())
+            }
+            None => self.scalar(expr, false),
+        }
+    }
+
+    fn order_key(&mut self, expr: &ast::Expr, names: &[String]) -> Result<OrderKey> {
+        match expr {
+            ast::Expr::Identifier(ident) => {
+                if let Some(i) = names.iter().position(|name| *name == ident.value) {
+                    return Ok(OrderKey::Output(i));
+                }
+            }
+            ast::Expr::Value(ast::Value::Number(n, _)) => {
+                return match n.parse::<usize>() {
+                    Ok(i) if (1..=names.len()).contains(&i) => Ok(OrderKey::Output(i - 1)),
+                    _ => bail!("ORDER BY {} is not a column of the result", n),
+                };
+            }
+            _ => {}
+        }
+        Ok(OrderKey::Expr(self.scalar(expr, true)?))
+    }
+
+    fn source(&self, table: &str) -> Result<usize> {
+        self.sources[..self.visible]
+            .iter()
+            .position(|s| s.name == table)
+            .ok_or_else(|| anyhow!("unknown table {:?}", table))
+    }
+
+    fn column(&self, ident: &str) -> Result<Scalar> {
+        let found: Vec<usize> = (0..self.visible)
+            .filter(|&i| self.sources[i].headers.iter().any(|h| h == ident))
+            .collect();
+        match found.as_slice() {
+            [i] => Ok(Scalar::Column(*i, ident.to_string())),
+            [] => {
+                let headers: Vec<&String> = self.sources[..self.visible]
+                    .iter()
+                    .flat_map(|s| &s.headers)
+                    .collect();
+                bail!("unknown column {:?}, expected one of {:?}", ident, headers)
+            }
+            _ => bail!(
+                "column {:?} is in more than one table, write <table>.{}",
+                ident,
+                ident
+            ),
+        }
+    }
+
+    fn scalar(&mut self, expr: &ast::Expr, allow_aggregates: bool) -> Result<Scalar> {
+        use ast::Expr as E;
+        let mut boxed = |expr: &ast::Expr| -> Result<Box<Scalar>> {
+            Ok(Box::new(self.scalar(expr, allow_aggregates)?))
+        };
+        Ok(match expr {
+            E::Identifier(ident) => return self.column(&ident.value),
+            E::CompoundIdentifier(idents) => match idents.as_slice() {
+                [table, column] => {
+                    let i = self.source(&table.value)?;
+                    if !self.sources[i].headers.contains(&column.value) {
+                        bail!("table {:?} has no column {:?}", table.value, column.value);
+                    }
+                    Scalar::Column(i, column.value.clone())
+                }
+                _ => bail!("unsupported column reference {}", expr),
+            },
+            E::Value(value) => Scalar::Literal(literal(value)?),
+            E::Nested(expr) => return self.scalar(expr, allow_aggregates),
+            E::BinaryOp { left, op, right } => {
+                Scalar::Binary(boxed(left)?, op.clone(), boxed(right)?)
+            }
+            E::UnaryOp { op, expr } => match op {
+                UnaryOperator::Not => Scalar::Not(boxed(expr)?),
+                UnaryOperator::Minus => Scalar::Neg(boxed(expr)?),
+                UnaryOperator::Plus => return self.scalar(expr, allow_aggregates),
+                op => bail!("unsupported operator {}", op),
+            },
+            E::IsNull(expr) => Scalar::IsNull(boxed(expr)?, false),
+            E::IsNotNull(expr) => Scalar::IsNull(boxed(expr)?, true),
+            E::InList {
+                expr,
+                list,
+                negated,
+            } => {
+                let list = list
+                    .iter()
+                    .map(|e| self.scalar(e, allow_aggregates))
+                    .collect::<Result<_>>()?;
+                Scalar::InList(
+                    Box::new(self.scalar(expr, allow_aggregates)?),
+                    list,
+                    *negated,
+                )
+            }
+            E::Between {
+                expr,
+                negated,
+                low,
+                high,
+            } => Scalar::Between(boxed(expr)?, boxed(low)?, boxed(high)?, *negated),
+            E::Like {
+                negated,
+                expr,
+                pattern,
+                ..
+            } => Scalar::Like(boxed(expr)?, like_regex(pattern, false)?, *negated),
+            E::ILike {
+                negated,
+                expr,
+                pattern,
+                ..
+            } => Scalar::Like(boxed(expr)?, like_regex(pattern, true)?, *negated),
+            E::Function(function) => return self.function(function, allow_aggregates),
+            expr => bail!("unsupported expression {}", expr),
+        })
+    }
+
+    fn function(&mut self, function: &ast::Function, allow_aggregates: bool) -> Result<Scalar> {
+        let name = ident_name(function.name.0.last()).to_lowercase();
+        let FunctionArguments::List(list) = &function.args else {
+            bail!("{}() needs parentheses", name);
+        };
+        let mut star = false;
+        let mut args = Vec::with_capacity(list.args.len());
+        for arg in &list.args {
+            match arg {
+                FunctionArg::Unnamed(FunctionArgExpr::Expr(expr)) => args.push(expr),
+                FunctionArg::Unnamed(FunctionArgExpr::Wildcard) if name == "count" => star = true,
+                arg => bail!("unsupported argument {} of {}()", arg, name),
+            }
+        }
+        match name.as_str() {
+            "count" | "sum" | "avg" | "min" | "max" => {
+                if !allow_aggregates {
+                    bail!("{}() can't be used in WHERE, GROUP BY or a join", name);
+                }
+                let arg = match (star, args.as_slice()) {
+                    (true, []) => None,
+                    (false, [arg]) => Some(
+                        self.scalar(arg, false)
+                            .map_err(|e| anyhow!("{}() takes a plain expression: {}", name, e))?,
+                    ),
+                    _ => bail!("{}() takes one argument", name),
+                };
+                let distinct = list.duplicate_treatment == Some(DuplicateTreatment::Distinct);
+                self.aggregates.push(Aggregate {
+                    func: name,
+                    arg,
+                    distinct,
+                });
+                Ok(Scalar::Aggregate(self.aggregates.len() - 1))
+            }
+            "lower" | "upper" | "length" | "abs" | "round" | "coalesce" => {
+                let args = args
+                    .into_iter()
+                    .map(|arg| self.scalar(arg, allow_aggregates))
+                    .collect::<Result<Vec<_>>>()?;
+                let arity_ok = match name.as_str() {
+                    "round" => matches!(args.len(), 1 | 2),
+                    "coalesce" => !args.is_empty(),
+                    _ => args.len() == 1,
+                };
+                if !arity_ok {
+                    bail!("wrong number of arguments for {}()", name);
+                }
+                Ok(Scalar::Call(name, args))
+            }
+            _ => bail!("unsupported function {}()", name),
+        }
+    }
+}
+
+fn literal(value: &ast::Value) -> Result<Value> {
+    Ok(match value {
+        ast::Value::Number(n, _) => match n.parse::<i64>() {
+            Ok(i) => i.into(),
+            Err(_) => number(n.parse()?),
+        },
+        ast::Value::SingleQuotedString(s) | ast::Value::DoubleQuotedString(s) => s.clone().into(),
+        ast::Value::Boolean(b) => (*b).into(),
+        ast::Value::Null => Value::Null,
+        value => bail!("unsupported literal {}", value),
+    })
+}
+
+/// Turn a LIKE pattern into an anchored regex, `%` matches any text and `_` one character.
+fn like_regex(pattern: &ast::Expr, ignore_case: bool) -> Result<Regex> {
+    let ast::Expr::Value(ast::Value::SingleQuotedString(pattern)) = pattern else {
+        bail!("LIKE needs a quoted pattern, not {}", pattern);
+    };
+    let mut re = String::from(if ignore_case { "(?is)^" } else { "(?s)^" });
+    for c in pattern.chars() {
+        match c {
+            '%' => re.push_str(".*"),
+            '_' => re.push('.'),
+            c => re.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    re.push('$');
+    Ok(Regex::new(&re)?)
+}
+
+impl Scalar {
+    /// Call `f` on this expression and every expression inside it, aggregates are left
+    /// as their index.
+    fn visit(&self, f: &mut dyn FnMut(&Scalar)) {
+        f(self);
+        match self {
+            Scalar::Column(..) | Scalar::Literal(_) | Scalar::Aggregate(_) => {}
+            Scalar::Binary(a, _, b) => {
+                a.visit(f);
+                b.visit(f);
+            }
+            Scalar::Not(e) | Scalar::Neg(e) | Scalar::IsNull(e, _) | Scalar::Like(e, _, _) => {
+                e.visit(f)
+            }
+            Scalar::InList(e, list, _) => {
+                e.visit(f);
+                list.iter().for_each(|item| item.visit(f));
+            }
+            Scalar::Between(e, low, high, _) => {
+                e.visit(f);
+                low.visit(f);
+                high.visit(f);
+            }
+            Scalar::Call(_, args) => args.iter().for_each(|arg| arg.visit(f)),
+        }
+    }
+
+    fn eval(&self, row: &[Option<&Value>], aggregates: &[Value]) -> Value {
+        match self {
+            Scalar::Column(i, column) => row[*i].map_or(Value::Null, |r| r[column].clone()),
+            Scalar::Literal(value) => value.clone(),
+            Scalar::Binary(a, op, b) => {
+                binary(&a.eval(row, aggregates), op, &b.eval(row, aggregates))
+            }
+            Scalar::Not(e) => (!truthy(&e.eval(row, aggregates))).into(),
+            Scalar::Neg(e) => {
+                cell_number(&e.eval(row, aggregates)).map_or(Value::Null, |n| number(-n))
+            }
+            Scalar::IsNull(e, negated) => (is_null(&e.eval(row, aggregates)) != *negated).into(),
+            Scalar::InList(e, list, negated) => {
+                let value = e.eval(row, aggregates);
+                let found = list
+                    .iter()
+                    .any(|item| compare(&value, CmpOp::Eq, &item.eval(row, aggregates)));
+                (found != *negated).into()
+            }
+            Scalar::Between(e, low, high, negated) => {
+                let value = e.eval(row, aggregates);
+                let inside = compare(&value, CmpOp::Ge, &low.eval(row, aggregates))
+                    && compare(&value, CmpOp::Le, &high.eval(row, aggregates));
+                (inside != *negated).into()
+            }
+            Scalar::Like(e, re, negated) => {
+                (re.is_match(&cell_text(&e.eval(row, aggregates))) != *negated).into()
+            }
+            Scalar::Call(name, args) => {
+                let args: Vec<Value> = args.iter().map(|a| a.eval(row, aggregates)).collect();
+                call(name, &args)
+            }
+            Scalar::Aggregate(i) => aggregates[*i].clone(),
+        }
+    }
+}
+
+impl Aggregate {
+    fn eval(&self, rows: &[Joined]) -> Value {
+        let Some(arg) = &self.arg else {
+            return rows.len().into();
+        };
+        // csv has no nulls, so empty cells don't count either
+        let mut values: Vec<Value> = rows
+            .iter()
+            .map(|row| arg.eval(row, &[]))
+            .filter(|value| !is_null(value))
+            .collect();
+        if self.distinct {
+            let mut seen = HashSet::new();
+            values.retain(|value| seen.insert(cell_text(value)));
+        }
+        let numbers = || values.iter().filter_map(cell_number);
+        match self.func.as_str() {
+            "count" => values.len().into(),
+            "sum" if numbers().next().is_some() => number(numbers().sum()),
+            "avg" if numbers().next().is_some() => {
+                number(numbers().sum::<f64>() / numbers().count() as f64)
+            }
+            "min" => values
+                .iter()
+                .min_by(|a, b| order_values(a, b))
+                .cloned()
+                .unwrap_or_default(),
+            "max" => values
+                .iter()
+                .max_by(|a, b| order_values(a, b))
+                .cloned()
+                .unwrap_or_default(),
+            _ => Value::Null,
+        }
+    }
+}
+
+fn binary(a: &Value, op: &BinaryOperator, b: &Value) -> Value {
+    let cmp = |op| compare(a, op, b).into();
+    let arithmetic = |f: fn(f64, f64) -> Option<f64>| match (cell_number(a), cell_number(b)) {
+        (Some(x), Some(y)) => f(x, y).map_or(Value::Null, number),
+        _ => Value::Null,
+    };
+    match op {
+        BinaryOperator::And => (truthy(a) && truthy(b)).into(),
+        BinaryOperator::Or => (truthy(a) || truthy(b)).into(),
+        BinaryOperator::Eq => cmp(CmpOp::Eq),
+        BinaryOperator::NotEq => cmp(CmpOp::Ne),
+        BinaryOperator::Lt => cmp(CmpOp::Lt),
+        BinaryOperator::LtEq => cmp(CmpOp::Le),
+        BinaryOperator::Gt => cmp(CmpOp::Gt),
+        BinaryOperator::GtEq => cmp(CmpOp::Ge),
+        BinaryOperator::Plus => arithmetic(|x, y| Some(x + y)),
+        BinaryOperator::Minus => arithmetic(|x, y| Some(x - y)),
+        BinaryOperator::Multiply => arithmetic(|x, y| Some(x * y)),
+        BinaryOperator::Divide => arithmetic(|x, y| (y != 0.0).then(|| x / y)),
+        BinaryOperator::Modulo => arithmetic(|x, y| (y != 0.0).then(|| x % y)),
+        BinaryOperator::StringConcat => format!("{}{}", cell_text(a), cell_text(b)).into(),
+        _ => Value::Null,
+    }
+}
+
+fn call(name: &str, args: &[Value]) -> Value {
+    let text = || cell_text(&args[0]);
+    match name {
+        "lower" => text().to_lowercase().into(),
+        "upper" => text().to_uppercase().into(),
+        "length" => text().chars().count().into(),
+        "abs" => cell_number(&args[0]).map_or(Value::Null, |n| number(n.abs())),
+        "round" => {
+            let digits = args.get(1).and_then(cell_number).unwrap_or(0.0);
+            let scale = 10f64.powi(digits as i32);
+            cell_number(&args[0]).map_or(Value::Null, |n| number((n * scale).round() / scale))
+        }
+        "coalesce" => args
+            .iter()
+            .find(|v| !is_null(v))
+            .cloned()
+            .unwrap_or_default(),
+        _ => Value::Null,
+    }
+}
+
+/// A json number, whole numbers without a trailing `.0`.
+fn number(n: f64) -> Value {
+    if n.fract() == 0.0 && n.abs() < i64::MAX as f64 {
+        (n as i64).into()
+    } else {
+        serde_json::Number::from_f64(n).map_or(Value::Null, Value::Number)
+    }
+}
+
+fn is_null(value: &Value) -> bool {
+    matches!(value, Value::Null) || matches!(value, Value::String(s) if s.is_empty())
+}
+
+fn truthy(value: &Value) -> bool {
+    match value {
+        Value::Bool(b) => *b,
+        Value::Null => false,
+        value => cell_number(value).map_or(!is_null(value), |n| n != 0.0),
+    }
+}
+
+/// Numbers in numeric order, anything else by its text.
+fn order_values(a: &Value, b: &Value) -> Ordering {
+    match (cell_number(a), cell_number(b)) {
+        (Some(x), Some(y)) => x.total_cmp(&y),
+        _ => cell_text(a).cmp(&cell_text(b)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn query(sql: &str) -> Result<Vec<Value>> {
+        let tables = [
+            ("players".to_string(), "assets/juventus.csv".to_string()),
+            ("old".to_string(), "fixtures/roster_old.csv".to_string()),
+            ("new".to_string(), "fixtures/roster_new.csv".to_string()),
+        ];
+        Ok(process_csv_query(sql, &tables, &DialectOptions::default())?.rows)
+    }
+
+    #[test]
+    fn test_select_where_order_limit() -> Result<()> {
+        let rows = query(
+            r#"SELECT Name, "Kit Number" AS kit FROM players
+               WHERE Position = 'Goalkeeper' ORDER BY kit DESC LIMIT 2"#,
+        )?;
+        assert_eq!(
+            rows,
+            [
+                json!({"Name": "Gianluigi Buffon", "kit": "77"}),
+                json!({"Name": "Mattia Perin", "kit": "37"}),
+            ]
+        );
+        let rows =
+            query("SELECT DISTINCT Nationality FROM players WHERE Name LIKE '%o%' ORDER BY 1")?;
+        assert!(rows.len() > 1);
+        assert_eq!(
+            rows.iter().filter(|r| r["Nationality"] == "Italy").count(),
+            1
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_group_by() -> Result<()> {
+        let rows = query(
+            r#"SELECT Position, count(*) AS players, max("Kit Number") AS top FROM players
+               GROUP BY Position HAVING count(*) > 2 ORDER BY players DESC, Position"#,
+        )?;
+        assert_eq!(
+            rows[0],
+            json!({"Position": "Central Midfield", "players": 6, "top": "30"})
+        );
+        assert!(rows.iter().all(|r| r["players"].as_u64() > Some(2)));
+
+        let rows = query(
+            r#"SELECT count(*), sum("Kit Number") AS kits FROM players WHERE Name = 'nobody'"#,
+        )?;
+        assert_eq!(rows, [json!({"count(*)": 0, "kits": null})]);
+
+        let by_position = |group_by: &str| {
+            query(&format!(
+                "SELECT Position AS pos, count(DISTINCT Name) AS n FROM old GROUP BY {} ORDER BY 1",
+                group_by
+            ))
+        };
+        let rows = by_position("1")?;
+        assert_eq!(
+            rows,
+            [
+                json!({"pos": "Forward", "n": 1}),
+                json!({"pos": "Goalkeeper", "n": 2}),
+            ]
+        );
+        assert_eq!(by_position("pos")?, rows);
+        assert!(by_position("3").is_err());
+        assert!(query("SELECT count(*) AS n FROM old GROUP BY 1").is_err());
+
+        let err = query("SELECT Name, count(*) FROM players").unwrap_err();
+        assert!(err.to_string().contains("\"Name\" must be in GROUP BY"));
+        assert!(query("SELECT Position FROM players GROUP BY Position ORDER BY Name").is_err());
+        assert!(query("SELECT upper(Position) FROM players GROUP BY Position").is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn test_join() -> Result<()> {
+        let rows = query(
+            "SELECT o.Name, o.Kit AS old_kit, n.Kit AS new_kit FROM old o
+             LEFT JOIN new n ON o.Name = n.Name WHERE n.Name IS NULL OR o.Kit <> n.Kit",
+        )?;
+        assert_eq!(rows.len(), 3);
+        assert_eq!(
+            rows[1],
+            json!({"Name": "Perin", "old_kit": "37", "new_kit": null})
+        );
+
+        let err = query("SELECT Name FROM old JOIN new ON old.Name = new.Name").unwrap_err();
+        assert!(err.to_string().contains("more than one table"));
+        assert!(query("SELECT * FROM missing").is_err());
+        assert!(query("SELECT Name FROM players WHERE count(*) > 1").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,175 @@
+use crate::utils::{get_writer, input_name};
+use anyhow::{anyhow, Result};
+use csv::{ErrorKind, Position};
+use serde::Serialize;
+use std::{fmt, io::Write};
+
+/// A csv row that couldn't be read or converted, and why.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct BadRow {
+    pub file: String,
+    /// line of the row, for json and yaml input its number in the array
+    pub line: u64,
+    /// byte offset of the start of the row
+    pub byte: u64,
+    /// 1-based index of the offending field, if it's known
+    pub field: Option<usize>,
+    pub column: Option<String>,
+    pub reason: String,
+}
+
+impl fmt::Display for BadRow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, byte {}", self.line, self.byte)?;
+        match (self.field, &self.column) {
+            (Some(field), Some(column)) => write!(f, ", field {} ({:?})", field, column)?,
+            (Some(field), None) => write!(f, ", field {}", field)?,
+            _ => {}
+        }
+        write!(f, ": {}", self.reason)
+    }
+}
+
+impl BadRow {
+    /// Describe a csv read error, errors that aren't about the row itself (e.g. io) are handed back.
+    pub fn from_csv_error(
+        input: &str,
+        err: csv::Error,
+        headers: &[String],
+    ) -> Result<Self, csv::Error> {
+        let (pos, field, reason) = match err.kind() {
+            ErrorKind::UnequalLengths {
+                pos,
+                expected_len,
+                len,
+            } => {
+                // the first missing field, or the first one too many
+                let field = (*expected_len).min(*len) as usize;
+                let reason = format!("expected {} fields, found {}", expected_len, len);
+                (pos.clone(), field, reason)
+            }
+            ErrorKind::Utf8 { pos, err } => {
+                let reason = format!("invalid utf-8 after {} bytes", err.valid_up_to());
+                (pos.clone(), err.field(), reason)
+            }
+            _ => return Err(err),
+        };
+        let pos = pos.unwrap_or_else(Position::new);
+        Ok(Self {
+            file: input_name(input).to_string(),
+            line: pos.line(),
+            byte: pos.byte(),
+            field: Some(field + 1),
+            column: headers.get(field).cloned(),
+            reason,
+        })
+    }
+
+    /// A cell of the row at `pos` that doesn't fit its column.
+    pub fn at_cell(
+        input: &str,
+        pos: &Position,
+        headers: &[String],
+        column: &str,
+        reason: String,
+    ) -> Self {
+        let field = headers.iter().position(|h| h == column);
+        Self {
+            file: input_name(input).to_string(),
+            line: pos.line(),
+            byte: pos.byte(),
+            field: field.map(|i| i + 1),
+            column: Some(column.to_string()),
+            reason,
+        }
+    }
+}
+
+/// Turn a csv read error into one naming the file, line, byte offset and field.
+pub fn describe_csv_error(input: &str, err: csv::Error, headers: &[String]) -> anyhow::Error {
+    match BadRow::from_csv_error(input, err, headers) {
+        Ok(bad) => anyhow!("{}: {}", bad.file, bad),
+        Err(err) => err.into(),
+    }
+}
+
+/// Collects bad rows when they should be skipped, or fails on the first one.
+#[derive(Debug)]
+pub struct Rejects {
+    skip: bool,
+    rows: Vec<BadRow>,
+}
+
+impl Rejects {
+    pub fn new(skip: bool) -> Self {
+        Self {
+            skip,
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn reject(&mut self, bad: BadRow) -> Result<()> {
+        if !self.skip {
+            return Err(anyhow!("{}: {}", bad.file, bad));
+        }
+        self.rows.push(bad);
+        Ok(())
+    }
+
+    /// Write the skipped rows to `report`, as json when it ends in `.json` and csv otherwise,
+    /// or list them on stderr when there's no report.
+    pub fn finish(self, report: Option<&str>) -> Result<()> {
+        match report {
+            Some(path) if path.to_ascii_lowercase().ends_with(".json") => {
+                let mut writer = get_writer(path)?;
+                serde_json::to_writer_pretty(&mut writer, &self.rows)?;
+                writer.flush()?;
+            }
+            Some(path) => {
+                let mut writer = csv::Writer::from_writer(get_writer(path)?);
+                if self.rows.is_empty() {
+                    writer.write_record(["file", "line", "byte", "field", "column", "reason"])?;
+                }
+                for row in &self.rows {
+                    writer.serialize(row)?;
+                }
+                writer.flush()?;
+            }
+            None => {
+                for row in &self.rows {
+                    eprintln!("skipped {}: {}", row.file, row);
+                }
+            }
+        }
+        if !self.rows.is_empty() {
+            eprintln!("skipped {} bad row(s)", self.rows.len());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use csv::ReaderBuilder;
+
+    #[test]
+    fn test_bad_row_from_csv_error() {
+        let headers = vec!["Name".to_string(), "Position".to_string()];
+        let input = "Name,Position\nBuffon,Goalkeeper\nPerin\nDybala,Forward,10\n";
+        let mut reader = ReaderBuilder::new().from_reader(input.as_bytes());
+        let bad: Vec<BadRow> = reader
+            .records()
+            .filter_map(|r| r.err())
+            .map(|e| BadRow::from_csv_error("-", e, &headers).unwrap())
+            .collect();
+        assert_eq!(
+            bad[0].to_string(),
+            "line 3, byte 32, field 2 (\"Position\"): expected 2 fields, found 1"
+        );
+        assert_eq!(
+            bad[1].to_string(),
+            "line 4, byte 38, field 3: expected 2 fields, found 3"
+        );
+    }
+}
@@ -0,0 +1,251 @@
+use crate::cli::ColumnType;
+use crate::process::csv_types::{infer_cell, merge_types};
+use anyhow::Result;
+use csv::{ReaderBuilder, StringRecord};
+use encoding_rs::Encoding;
+use encoding_rs_io::DecodeReaderBytesBuilder;
+use std::collections::HashSet;
+use std::io::{Cursor, Read};
+
+const SAMPLE_SIZE: u64 = 64 * 1024;
+const SAMPLE_LINES: usize = 20;
+const DELIMITERS: &[u8] = b",;\t|:";
+const QUOTES: &[u8] = b"\"'";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Dialect {
+    pub delimiter: u8,
+    pub quote: u8,
+    pub has_header: bool,
+}
+
+/// Dialect settings given on the command line, unset fields fall back to sniffing or the defaults.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DialectOptions {
+    pub delimiter: Option<u8>,
+    pub quote: Option<u8>,
+    pub header: Option<bool>,
+    pub sniff: bool,
+    /// input encoding, utf-8 or whatever a byte order mark says when unset
+    pub encoding: Option<&'static Encoding>,
+}
+
+impl Default for Dialect {
+    fn default() -> Self {
+        Self {
+            delimiter: b',',
+            quote: b'"',
+            has_header: true,
+        }
+    }
+}
+
+impl DialectOptions {
+    /// Work out the dialect, peeking at the beginning of `reader` when sniffing is on.
+    /// The returned reader still yields the whole input.
+    pub fn resolve(&self, reader: Box<dyn Read>) -> Result<(Box<dyn Read>, Dialect)> {
+        let reader = self.decode(reader);
+        let (reader, base) = if self.sniff {
+            let mut sample = Vec::new();
+            let mut reader = reader;
+            reader.by_ref().take(SAMPLE_SIZE).read_to_end(&mut sample)?;
+            let dialect = sniff(&sample);
+            let reader: Box<dyn Read> = Box::new(Cursor::new(sample).chain(reader));
+            (reader, dialect)
+        } else {
+            (reader, Dialect::default())
+        };
+        let dialect = Dialect {
+            delimiter: self.delimiter.unwrap_or(base.delimiter),
+            quote: self.quote.unwrap_or(base.quote),
+            has_header: self.header.unwrap_or(base.has_header),
+        };
+        Ok((reader, dialect))
+    }
+
+    /// Transcode `reader` to utf-8 and drop any byte order mark, a mark wins over `encoding`.
+    /// Input without a mark or encoding is passed through as is.
+    pub fn decode(&self, reader: Box<dyn Read>) -> Box<dyn Read> {
+        Box::new(
+            DecodeReaderBytesBuilder::new()
+                .encoding(self.encoding)
+                .bom_override(true)
+                .strip_bom(true)
+                .build(reader),
+        )
+    }
+}
+
+/// Guess the delimiter, quote character and header row from the first lines of a csv file.
+pub fn sniff(sample: &[u8]) -> Dialect {
+    // drop a trailing partial line, unless the sample is all we have
+    let sample = match sample.iter().rposition(|&b| b == b'\n') {
+        Some(pos) if sample.len() as u64 >= SAMPLE_SIZE => &sample[..=pos],
+        _ => sample,
+    };
+    let quote = sniff_quote(sample);
+    let mut best = (b',', 0);
+    for &delimiter in DELIMITERS {
+        let records = parse_sample(sample, delimiter, quote);
+        let Some(first) = records.first() else {
+            continue;
+        };
+        let width = first.len();
+        if width > best.1 && records.iter().all(|r| r.len() == width) {
+            best = (delimiter, width);
+        }
+    }
+    let delimiter = best.0;
+    let records = parse_sample(sample, delimiter, quote);
+    Dialect {
+        delimiter,
+        quote,
+        has_header: sniff_header(&records),
+    }
+}
+
+fn parse_sample(sample: &[u8], delimiter: u8, quote: u8) -> Vec<StringRecord> {
+    ReaderBuilder::new()
+        .delimiter(delimiter)
+        .quote(quote)
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(sample)
+        .records()
+        .take(SAMPLE_LINES)
+        .map_while(Result::ok)
+        .collect()
+}
+
+/// Pick the quote character that most often opens a field.
+fn sniff_quote(sample: &[u8]) -> u8 {
+    let opens = |quote: u8| {
+        sample
+            .windows(2)
+            .filter(|w| w[1] == quote && (w[0] == b'\n' || DELIMITERS.contains(&w[0])))
+            .count()
+            + usize::from(sample.first() == Some(&quote))
+    };
+    QUOTES
+        .iter()
+        .copied()
+        .max_by_key(|&q| (opens(q), q == b'"'))
+        .unwrap_or(b'"')
+}
+
+/// A header row is assumed when the first row has a different type than the rest of a column,
+/// or, for all-text data, when its cells are non-empty and never repeated below.
+fn sniff_header(records: &[StringRecord]) -> bool {
+    let Some((first, rest)) = records.split_first() else {
+        return true;
+    };
+    if rest.is_empty() {
+        return true;
+    }
+    let mut all_text = true;
+    for (col, cell) in first.iter().enumerate() {
+        let body = rest
+            .iter()
+            .map(|r| infer_cell(r.get(col).unwrap_or_default()))
+            .fold(None, merge_types);
+        match body {
+            Some(ColumnType::String) | None => {}
+            Some(_) => {
+                all_text = false;
+                if infer_cell(cell) == Some(ColumnType::String) {
+                    return true;
+                }
+            }
+        }
+    }
+    if !all_text {
+        return false;
+    }
+    let mut seen = HashSet::new();
+    first.iter().enumerate().all(|(col, cell)| {
+        !cell.is_empty() && seen.insert(cell) && rest.iter().all(|r| r.get(col) != Some(cell))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decoded(opts: DialectOptions, input: Vec<u8>) -> Result<String> {
+        let mut text = String::new();
+        opts.decode(Box::new(Cursor::new(input)))
+            .read_to_string(&mut text)?;
+        Ok(text)
+    }
+
+    #[test]
+    fn test_decode_encodings() -> Result<()> {
+        let opts = DialectOptions::default();
+        let utf8_bom = b"\xef\xbb\xbfName,Kit\nBuffon,77\n".to_vec();
+        assert_eq!(decoded(opts, utf8_bom)?, "Name,Kit\nBuffon,77\n");
+
+        let mut utf16 = vec![0xff, 0xfe];
+        utf16.extend(
+            "Name\nKrzysztof Pi\u{105}tek\n"
+                .encode_utf16()
+                .flat_map(u16::to_le_bytes),
+        );
+        assert_eq!(decoded(opts, utf16)?, "Name\nKrzysztof Pi\u{105}tek\n");
+
+        let cp1252 = DialectOptions {
+            encoding: Encoding::for_label(b"windows-1252"),
+            ..opts
+        };
+        let input = b"Name\nJos\xe9 \x80\n".to_vec();
+        assert_eq!(decoded(cp1252, input)?, "Name\nJos\u{e9} \u{20ac}\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_sniff_semicolon_with_header() {
+        let sample =
+            b"Name;Position;Kit Number\nBuffon;Goalkeeper;77\n\"Perin; Mattia\";Goalkeeper;37\n";
+        assert_eq!(
+            sniff(sample),
+            Dialect {
+                delimiter: b';',
+                quote: b'"',
+                has_header: true
+            }
+        );
+    }
+
+    #[test]
+    fn test_sniff_tab_without_header() {
+        let sample = b"Buffon\tGoalkeeper\t77\nPerin\tGoalkeeper\t37\nDybala\tForward\t10\n";
+        let dialect = sniff(sample);
+        assert_eq!(dialect.delimiter, b'\t');
+        assert!(!dialect.has_header);
+    }
+
+    #[test]
+    fn test_sniff_single_quote() {
+        let sample = b"'Name','Team'\n'Buffon, Gianluigi','Juventus'\n";
+        let dialect = sniff(sample);
+        assert_eq!(dialect.delimiter, b',');
+        assert_eq!(dialect.quote, b'\'');
+    }
+
+    #[test]
+    fn test_explicit_options_win() -> Result<()> {
+        let opts = DialectOptions {
+            delimiter: Some(b'|'),
+            header: Some(false),
+            sniff: true,
+            ..Default::default()
+        };
+        let reader: Box<dyn Read> = Box::new(&b"a;b\n1;2\n"[..]);
+        let (mut reader, dialect) = opts.resolve(reader)?;
+        assert_eq!(dialect.delimiter, b'|');
+        assert!(!dialect.has_header);
+        let mut content = String::new();
+        reader.read_to_string(&mut content)?;
+        assert_eq!(content, "a;b\n1;2\n");
+        Ok(())
+    }
+}
@@ -0,0 +1,158 @@
+use crate::cli::ColumnType;
+use crate::process::csv_convert::{csv_headers, csv_reader};
+use crate::process::csv_rejects::describe_csv_error;
+use crate::process::csv_sniff::DialectOptions;
+use crate::process::csv_types::{infer_cell, merge_types};
+use crate::utils::get_reader;
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Profile of a single csv column.
+#[derive(Debug, Serialize, PartialEq)]
+pub struct ColumnStats {
+    pub column: String,
+    /// inferred type, `empty` when the column has no values at all
+    #[serde(rename = "type")]
+    pub ty: String,
+    pub count: usize,
+    pub nulls: usize,
+    pub distinct: usize,
+    pub min: Option<String>,
+    pub max: Option<String>,
+    pub mean: Option<f64>,
+    pub median: Option<f64>,
+    pub top: Vec<TopValue>,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct TopValue {
+    pub value: String,
+    pub count: usize,
+}
+
+#[derive(Debug, Default)]
+struct ColumnProfile {
+    count: usize,
+    nulls: usize,
+    ty: Option<ColumnType>,
+    values: HashMap<String, usize>,
+    numbers: Vec<f64>,
+}
+
+impl ColumnProfile {
+    fn add(&mut self, cell: &str) {
+        self.count += 1;
+        if cell.is_empty() {
+            self.nulls += 1;
+            return;
+        }
+        self.ty = merge_types(self.ty, infer_cell(cell));
+        if let Ok(n) = cell.parse::<f64>() {
+            self.numbers.push(n);
+        }
+        *self.values.entry(cell.to_string()).or_default() += 1;
+    }
+
+    fn finish(mut self, column: String, top: usize) -> ColumnStats {
+        let numeric = matches!(self.ty, Some(ColumnType::Int | ColumnType::Float));
+        let (min, max, mean, median) = if numeric {
+            self.numbers.sort_by(f64::total_cmp);
+            let n = self.numbers.len();
+            let mean = self.numbers.iter().sum::<f64>() / n as f64;
+            let median = if n % 2 == 1 {
+                self.numbers[n / 2]
+            } else {
+                (self.numbers[n / 2 - 1] + self.numbers[n / 2]) / 2.0
+            };
+            let fmt = |v: f64| v.to_string();
+            (
+                self.numbers.first().copied().map(fmt),
+                self.numbers.last().copied().map(fmt),
+                Some(mean),
+                Some(median),
+            )
+        } else {
+            let min = self.values.keys().min().cloned();
+            let max = self.values.keys().max().cloned();
+            (min, max, None, None)
+        };
+        let mut top_values: Vec<TopValue> = self
+            .values
+            .iter()
+            .map(|(value, &count)| TopValue {
+                value: value.clone(),
+                count,
+            })
+            .collect();
+        top_values.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.value.cmp(&b.value)));
+        top_values.truncate(top);
+        ColumnStats {
+            column,
+            ty: self.ty.map_or("empty", Into::into).to_string(),
+            count: self.count,
+            nulls: self.nulls,
+            distinct: self.values.len(),
+            min,
+            max,
+            mean,
+            median,
+            top: top_values,
+        }
+    }
+}
+
+/// Profile every column of a csv file, keeping the `top` most frequent values of each.
+pub fn process_csv_stats(
+    input: &str,
+    dialect: &DialectOptions,
+    top: usize,
+) -> Result<Vec<ColumnStats>> {
+    let (reader, dialect) = dialect.resolve(get_reader(input)?)?;
+    let mut reader = csv_reader(reader, dialect);
+    let headers = csv_headers(&mut reader)?;
+    let mut profiles: Vec<ColumnProfile> = headers.iter().map(|_| Default::default()).collect();
+    for record in reader.records() {
+        let record = record.map_err(|e| describe_csv_error(input, e, &headers))?;
+        for (profile, cell) in profiles.iter_mut().zip(record.iter()) {
+            profile.add(cell);
+        }
+    }
+    Ok(headers
+        .into_iter()
+        .zip(profiles)
+        .map(|(column, profile)| profile.finish(column, top))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_process_csv_stats() -> Result<()> {
+        let stats = process_csv_stats("assets/juventus.csv", &DialectOptions::default(), 2)?;
+        assert_eq!(stats.len(), 5);
+
+        let position = &stats[1];
+        assert_eq!(position.ty, "string");
+        assert_eq!(position.count, 27);
+        assert_eq!(position.distinct, 10);
+        assert_eq!(position.mean, None);
+        assert_eq!(
+            position.top[0],
+            TopValue {
+                value: "Central Midfield".into(),
+                count: 6
+            }
+        );
+
+        let kit = &stats[4];
+        assert_eq!(kit.ty, "int");
+        assert_eq!(kit.nulls, 0);
+        assert_eq!(kit.min.as_deref(), Some("1"));
+        assert_eq!(kit.max.as_deref(), Some("77"));
+        assert_eq!(kit.median, Some(15.0));
+        Ok(())
+    }
+}
@@ -0,0 +1,236 @@
+use crate::cli::{ColumnType, InferMode};
+use anyhow::{anyhow, Result};
+use serde_json::{Number, Value};
+use std::{collections::HashMap, fmt};
+
+/// Guess the narrowest type a single cell fits, empty cells are null.
+pub fn infer_cell(cell: &str) -> Option<ColumnType> {
+    if cell.is_empty() {
+        None
+    } else if cell.parse::<i64>().is_ok() {
+        Some(ColumnType::Int)
+    } else if cell.parse::<f64>().is_ok_and(f64::is_finite) {
+        Some(ColumnType::Float)
+    } else if cell == "true" || cell == "false" {
+        Some(ColumnType::Bool)
+    } else {
+        Some(ColumnType::String)
+    }
+}
+
+/// Widen two cell types into one that holds both, ints widen to floats and anything else to string.
+pub fn merge_types(a: Option<ColumnType>, b: Option<ColumnType>) -> Option<ColumnType> {
+    match (a, b) {
+        (None, t) | (t, None) => t,
+        (Some(a), Some(b)) if a == b => Some(a),
+        (Some(ColumnType::Int), Some(ColumnType::Float))
+        | (Some(ColumnType::Float), Some(ColumnType::Int)) => Some(ColumnType::Float),
+        _ => Some(ColumnType::String),
+    }
+}
+
+pub fn convert_cell(cell: &str, ty: ColumnType) -> Result<Value> {
+    if cell.is_empty() {
+        return Ok(Value::Null);
+    }
+    let value = match ty {
+        ColumnType::String => Value::String(cell.to_string()),
+        ColumnType::Int => Value::Number(cell.parse::<i64>()?.into()),
+        ColumnType::Float => Number::from_f64(cell.parse::<f64>()?)
+            .map(Value::Number)
+            .ok_or_else(|| anyhow!("{} is not a finite number", cell))?,
+        ColumnType::Bool => Value::Bool(cell.parse::<bool>()?),
+    };
+    Ok(value)
+}
+
+/// The type of each column, `None` for columns without any values.
+pub type ColumnTypes = HashMap<String, Option<ColumnType>>;
+
+/// Infer the type of every column from all of its cells.
+pub fn scan_types<'a>(rows: impl IntoIterator<Item = &'a Value>) -> ColumnTypes {
+    let mut column_types = ColumnTypes::new();
+    for row in rows {
+        for (key, cell) in row.as_object().into_iter().flatten() {
+            let ty = infer_cell(cell.as_str().unwrap_or_default());
+            let entry = column_types.entry(key.clone()).or_default();
+            *entry = merge_types(*entry, ty);
+        }
+    }
+    column_types
+}
+
+/// Combine the scans of two sets of rows.
+pub fn merge_scans(mut a: ColumnTypes, b: ColumnTypes) -> ColumnTypes {
+    for (key, ty) in b {
+        let entry = a.entry(key).or_default();
+        *entry = merge_types(*entry, ty);
+    }
+    a
+}
+
+/// A cell that doesn't fit the type of its column.
+#[derive(Debug)]
+pub struct CellError {
+    pub column: String,
+    pub reason: String,
+}
+
+impl fmt::Display for CellError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "column {:?}: {}", self.column, self.reason)
+    }
+}
+
+impl std::error::Error for CellError {}
+
+/// Converts the string cells of csv rows into typed values.
+///
+/// Columns listed in `types` are always converted to the given type, the rest are
+/// inferred per cell or from the whole column depending on `mode`.
+#[derive(Debug)]
+pub struct RowTyper {
+    mode: InferMode,
+    column_types: ColumnTypes,
+}
+
+impl RowTyper {
+    /// `scan` holds the rows a column-wide inference looks at, it's ignored in the other modes.
+    pub fn new<'a>(
+        headers: &[String],
+        mode: InferMode,
+        types: &[(String, ColumnType)],
+        scan: impl IntoIterator<Item = &'a Value>,
+    ) -> Result<Self> {
+        let scanned = if mode == InferMode::Column {
+            scan_types(scan)
+        } else {
+            ColumnTypes::new()
+        };
+        Self::with_scanned(headers, mode, types, scanned)
+    }
+
+    /// Like [`RowTyper::new`], with the column types already worked out by [`scan_types`].
+    pub fn with_scanned(
+        headers: &[String],
+        mode: InferMode,
+        types: &[(String, ColumnType)],
+        scanned: ColumnTypes,
+    ) -> Result<Self> {
+        let mut column_types = scanned;
+        for (name, ty) in types {
+            if !headers.contains(name) {
+                return Err(anyhow!("--types refers to unknown column {:?}", name));
+            }
+            column_types.insert(name.clone(), Some(*ty));
+        }
+        Ok(Self { mode, column_types })
+    }
+
+    pub fn is_noop(&self) -> bool {
+        self.mode == InferMode::Off && self.column_types.is_empty()
+    }
+
+    /// Type a single row, failing on the first cell that doesn't fit its column.
+    pub fn apply(&self, row: &mut Value) -> Result<(), CellError> {
+        if self.is_noop() {
+            return Ok(());
+        }
+        let Some(obj) = row.as_object_mut() else {
+            return Ok(());
+        };
+        for (key, value) in obj.iter_mut() {
+            let Value::String(cell) = value else {
+                continue;
+            };
+            let ty = match self.column_types.get(key) {
+                Some(ty) => *ty,
+                None if self.mode == InferMode::Off => continue,
+                None => infer_cell(cell),
+            };
+            let typed = match ty {
+                Some(ty) => convert_cell(cell, ty).map_err(|e| CellError {
+                    column: key.clone(),
+                    reason: format!("can't read {:?} as {}: {}", cell, ty, e),
+                })?,
+                None => Value::Null,
+            };
+            *value = typed;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn headers() -> Vec<String> {
+        vec!["Name".into(), "Kit Number".into(), "Score".into()]
+    }
+
+    fn apply_types(
+        rows: &mut [Value],
+        headers: &[String],
+        mode: InferMode,
+        types: &[(String, ColumnType)],
+    ) -> Result<()> {
+        let typer = RowTyper::new(headers, mode, types, rows.iter())?;
+        for (i, row) in rows.iter_mut().enumerate() {
+            typer
+                .apply(row)
+                .map_err(|e| anyhow!("row {}, {}", i + 1, e))?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_infer_per_cell() -> Result<()> {
+        let mut rows = vec![
+            json!({"Name": "Buffon", "Kit Number": "77", "Score": "1.5"}),
+            json!({"Name": "true", "Kit Number": "", "Score": "2"}),
+        ];
+        apply_types(&mut rows, &headers(), InferMode::Cell, &[])?;
+        assert_eq!(
+            rows[0],
+            json!({"Name": "Buffon", "Kit Number": 77, "Score": 1.5})
+        );
+        assert_eq!(
+            rows[1],
+            json!({"Name": true, "Kit Number": null, "Score": 2})
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_infer_per_column() -> Result<()> {
+        let mut rows = vec![
+            json!({"Name": "Buffon", "Kit Number": "77", "Score": "1.5"}),
+            json!({"Name": "true", "Kit Number": "", "Score": "2"}),
+        ];
+        apply_types(&mut rows, &headers(), InferMode::Column, &[])?;
+        assert_eq!(
+            rows[1],
+            json!({"Name": "true", "Kit Number": null, "Score": 2.0})
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_type_overrides() -> Result<()> {
+        let mut rows = vec![json!({"Name": "Buffon", "Kit Number": "77", "Score": "1"})];
+        let types = vec![("Score".to_string(), ColumnType::Float)];
+        apply_types(&mut rows, &headers(), InferMode::Off, &types)?;
+        assert_eq!(
+            rows[0],
+            json!({"Name": "Buffon", "Kit Number": "77", "Score": 1.0})
+        );
+
+        let mut rows = vec![json!({"Name": "Buffon", "Kit Number": "77", "Score": "1"})];
+        let types = vec![("Name".to_string(), ColumnType::Int)];
+        let err = apply_types(&mut rows, &headers(), InferMode::Off, &types).unwrap_err();
+        assert!(err.to_string().starts_with("row 1, column \"Name\""));
+        Ok(())
+    }
+}
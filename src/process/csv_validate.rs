@@ -0,0 +1,338 @@
+use crate::cli::ColumnType;
+use crate::process::csv_convert::{csv_headers, csv_reader};
+use crate::process::csv_filter::cell_text;
+use crate::process::csv_rejects::describe_csv_error;
+use crate::process::csv_sniff::DialectOptions;
+use crate::process::csv_types::convert_cell;
+use crate::utils::{get_file_content, get_reader};
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+
+/// The simple column spec, e.g.
+///
+/// ```yaml
+/// strict: true
+/// columns:
+///   Kit Number: { type: int, required: true, min: 1, max: 99 }
+///   Position: { enum: [Goalkeeper, Centre-Back] }
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ColumnSpecFile {
+    columns: serde_json::Map<String, Value>,
+    /// reject columns that aren't listed
+    #[serde(default)]
+    strict: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ColumnSpec {
+    #[serde(rename = "type")]
+    ty: Option<String>,
+    #[serde(default)]
+    required: bool,
+    regex: Option<String>,
+    min: Option<f64>,
+    max: Option<f64>,
+    #[serde(rename = "enum")]
+    allowed: Option<Vec<Value>>,
+}
+
+#[derive(Debug)]
+struct ColumnRule {
+    column: String,
+    ty: Option<ColumnType>,
+    required: bool,
+    regex: Option<Regex>,
+    min: Option<f64>,
+    max: Option<f64>,
+    allowed: Option<Vec<String>>,
+}
+
+#[derive(Debug)]
+pub struct Schema {
+    rules: Vec<ColumnRule>,
+    strict: bool,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct Violation {
+    pub line: u64,
+    pub column: String,
+    pub reason: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {:?}: {}",
+            self.line, self.column, self.reason
+        )
+    }
+}
+
+impl Schema {
+    /// Load a yaml/json column spec, or a json schema describing one row object.
+    pub fn load(path: &str) -> Result<Self> {
+        let content = get_file_content(path)?;
+        // yaml is a superset of json, so one parser reads both
+        let doc: Value = serde_yaml::from_slice(&content)?;
+        if doc.get("properties").is_some() || doc.get("$schema").is_some() {
+            Self::from_json_schema(doc)
+        } else {
+            Self::from_column_spec(serde_json::from_value(doc)?)
+        }
+    }
+
+    fn from_column_spec(spec: ColumnSpecFile) -> Result<Self> {
+        let rules = spec
+            .columns
+            .into_iter()
+            .map(|(column, spec)| {
+                let spec: ColumnSpec = serde_json::from_value(spec)
+                    .map_err(|e| anyhow!("column {:?}: {}", column, e))?;
+                ColumnRule::new(column, spec)
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            rules,
+            strict: spec.strict,
+        })
+    }
+
+    /// Only the flat `properties` part of json schema is understood: type, pattern,
+    /// minimum, maximum and enum, plus `required` and `additionalProperties: false`.
+    fn from_json_schema(doc: Value) -> Result<Self> {
+        let required: Vec<&str> = doc["required"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .collect();
+        let properties = doc["properties"]
+            .as_object()
+            .ok_or_else(|| anyhow!("json schema needs an object of properties"))?;
+        let rules = properties
+            .iter()
+            .map(|(column, prop)| {
+                let types: Vec<&str> = match &prop["type"] {
+                    Value::String(t) => vec![t.as_str()],
+                    Value::Array(ts) => ts.iter().filter_map(Value::as_str).collect(),
+                    _ => vec![],
+                };
+                let ty = types
+                    .iter()
+                    .find(|&&t| t != "null")
+                    .map(|t| match *t {
+                        "integer" => Ok("int"),
+                        "number" => Ok("float"),
+                        "boolean" => Ok("bool"),
+                        "string" => Ok("string"),
+                        t => Err(anyhow!("column {:?}: unsupported type {:?}", column, t)),
+                    })
+                    .transpose()?;
+                let spec = ColumnSpec {
+                    ty: ty.map(String::from),
+                    required: required.contains(&column.as_str()),
+                    regex: prop["pattern"].as_str().map(String::from),
+                    min: prop["minimum"].as_f64(),
+                    max: prop["maximum"].as_f64(),
+                    allowed: prop["enum"].as_array().cloned(),
+                };
+                ColumnRule::new(column.clone(), spec)
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            rules,
+            strict: doc["additionalProperties"] == Value::Bool(false),
+        })
+    }
+
+    fn check_headers(&self, headers: &[String], violations: &mut Vec<Violation>) {
+        for rule in self.rules.iter().filter(|r| r.required) {
+            if !headers.contains(&rule.column) {
+                violations.push(Violation {
+                    line: 1,
+                    column: rule.column.clone(),
+                    reason: "required column is missing".into(),
+                });
+            }
+        }
+        if self.strict {
+            for header in headers {
+                if !self.rules.iter().any(|r| &r.column == header) {
+                    violations.push(Violation {
+                        line: 1,
+                        column: header.clone(),
+                        reason: "column isn't part of the schema".into(),
+                    });
+                }
+            }
+        }
+    }
+}
+
+impl ColumnRule {
+    fn new(column: String, spec: ColumnSpec) -> Result<Self> {
+        let context = |e: anyhow::Error| anyhow!("column {:?}: {}", column, e);
+        let ty = spec
+            .ty
+            .map(|t| t.parse::<ColumnType>())
+            .transpose()
+            .map_err(context)?;
+        let regex = spec
+            .regex
+            .map(|r| Regex::new(&r))
+            .transpose()
+            .map_err(|e| context(e.into()))?;
+        Ok(Self {
+            ty,
+            required: spec.required,
+            regex,
+            min: spec.min,
+            max: spec.max,
+            allowed: spec
+                .allowed
+                .map(|values| values.iter().map(cell_text).collect()),
+            column,
+        })
+    }
+
+    /// Why `cell` breaks the rule, if it does.
+    fn check(&self, cell: &str) -> Option<String> {
+        if cell.is_empty() {
+            return self.required.then(|| "required value is empty".into());
+        }
+        if let Some(ty) = self.ty {
+            if convert_cell(cell, ty).is_err() {
+                return Some(format!("{:?} is not a valid {}", cell, ty));
+            }
+        }
+        if let Some(regex) = &self.regex {
+            if !regex.is_match(cell) {
+                return Some(format!("{:?} doesn't match /{}/", cell, regex));
+            }
+        }
+        if let Some(allowed) = &self.allowed {
+            if !allowed.iter().any(|a| a == cell) {
+                return Some(format!("{:?} is not one of {:?}", cell, allowed));
+            }
+        }
+        if self.min.is_some() || self.max.is_some() {
+            let Ok(n) = cell.parse::<f64>() else {
+                return Some(format!("{:?} is not a number", cell));
+            };
+            if self.min.is_some_and(|min| n < min) {
+                return Some(format!("{} is below the minimum {}", cell, self.min?));
+            }
+            if self.max.is_some_and(|max| n > max) {
+                return Some(format!("{} is above the maximum {}", cell, self.max?));
+            }
+        }
+        None
+    }
+}
+
+/// Check every row of a csv file against a schema, returning all violations found.
+pub fn process_csv_validate(
+    input: &str,
+    dialect: &DialectOptions,
+    schema: &Schema,
+) -> Result<Vec<Violation>> {
+    let (reader, dialect) = dialect.resolve(get_reader(input)?)?;
+    let mut reader = csv_reader(reader, dialect);
+    let headers = csv_headers(&mut reader)?;
+    let mut violations = Vec::new();
+    schema.check_headers(&headers, &mut violations);
+    let rules: Vec<(usize, &ColumnRule)> = schema
+        .rules
+        .iter()
+        .filter_map(|rule| Some((headers.iter().position(|h| *h == rule.column)?, rule)))
+        .collect();
+    for record in reader.records() {
+        let record = record.map_err(|e| describe_csv_error(input, e, &headers))?;
+        let line = record.position().map_or(0, |p| p.line());
+        for &(index, rule) in &rules {
+            if let Some(reason) = rule.check(record.get(index).unwrap_or_default()) {
+                violations.push(Violation {
+                    line,
+                    column: rule.column.clone(),
+                    reason,
+                });
+            }
+        }
+    }
+    Ok(violations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_with_column_spec() -> Result<()> {
+        let schema = Schema::load("fixtures/juventus.schema.yaml")?;
+        let violations =
+            process_csv_validate("assets/juventus.csv", &DialectOptions::default(), &schema)?;
+        let found: Vec<(u64, &str)> = violations
+            .iter()
+            .map(|v| (v.line, v.column.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            [(4, "Kit Number"), (21, "Position"), (22, "Position")]
+        );
+        assert_eq!(violations[0].reason, "77 is above the maximum 40");
+        assert!(violations[1]
+            .reason
+            .starts_with("\"Left Winger\" is not one of"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_with_json_schema() -> Result<()> {
+        let schema = Schema::load("fixtures/juventus.schema.json")?;
+        let violations =
+            process_csv_validate("assets/juventus.csv", &DialectOptions::default(), &schema)?;
+        assert_eq!(
+            violations,
+            [
+                Violation {
+                    line: 1,
+                    column: "Nationality".into(),
+                    reason: "column isn't part of the schema".into(),
+                },
+                Violation {
+                    line: 12,
+                    column: "Name".into(),
+                    reason: "\"Danilo\" doesn't match /^\\S+ \\S+/".into(),
+                },
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_rule_check() -> Result<()> {
+        let rule = ColumnRule::new(
+            "Kit Number".into(),
+            ColumnSpec {
+                ty: Some("int".into()),
+                required: true,
+                ..Default::default()
+            },
+        )?;
+        assert_eq!(rule.check("7"), None);
+        assert_eq!(rule.check("").as_deref(), Some("required value is empty"));
+        assert_eq!(
+            rule.check("7.5").as_deref(),
+            Some("\"7.5\" is not a valid int")
+        );
+        Ok(())
+    }
+}
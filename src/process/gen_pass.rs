@@ -1,43 +1,512 @@
-use anyhow::Ok;
+use crate::utils::get_file_content;
+use anyhow::{bail, Result};
 use rand::prelude::SliceRandom;
+use serde::{Deserialize, Serialize};
+use zxcvbn::zxcvbn;
 
 const UPPER: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ";
 const LOWER: &[u8] = b"abcdefghijkmnpqrstuvwxyz";
 const NUMBER: &[u8] = b"123456789";
 const SYMBOL: &[u8] = b"!@#$%^&*_";
 
-pub fn process_genpass(
-    length: u8,
-    upper: bool,
-    lower: bool,
-    number: bool,
-    symbol: bool,
-) -> anyhow::Result<String> {
+/// Passwords are drawn again when one breaks a rule that can't be fixed in place.
+const MAX_ATTEMPTS: usize = 1000;
+
+/// A generated password with its entropy in bits and zxcvbn score.
+#[derive(Debug, Serialize)]
+pub struct GeneratedPassword {
+    pub password: String,
+    pub entropy: f64,
+    pub score: u8,
+}
+
+/// Rules a generated password must follow, e.g. in yaml
+///
+/// ```yaml
+/// length: 20
+/// exclude: "$"
+/// start_with_letter: true
+/// min_number: 2
+/// max_repeat: 3
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PasswordPolicy {
+    pub length: Option<usize>,
+    pub uppercase: bool,
+    pub lowercase: bool,
+    pub number: bool,
+    pub symbols: bool,
+    /// characters to draw from instead of the classes above, they still count
+    /// towards the class minimums by what they are
+    pub alphabet: Option<String>,
+    /// characters that are never used
+    pub exclude: String,
+    /// minimums per class, one of each enabled class when not set
+    pub min_upper: Option<usize>,
+    pub min_lower: Option<usize>,
+    pub min_number: Option<usize>,
+    pub min_symbol: Option<usize>,
+    pub start_with_letter: bool,
+    /// longest run of one repeated character
+    pub max_repeat: Option<usize>,
+}
+
+/// The characters a policy allows, split into classes.
+#[derive(Debug)]
+struct CharClasses {
+    all: Vec<char>,
+    /// upper, lower, number and symbol characters, each with its minimum count
+    classes: [(&'static str, usize, Vec<char>); 4],
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self {
+            length: None,
+            uppercase: true,
+            lowercase: true,
+            number: true,
+            symbols: true,
+            alphabet: None,
+            exclude: String::new(),
+            min_upper: None,
+            min_lower: None,
+            min_number: None,
+            min_symbol: None,
+            start_with_letter: false,
+            max_repeat: None,
+        }
+    }
+}
+
+impl PasswordPolicy {
+    /// Load a yaml or json policy file.
+    pub fn load(path: &str) -> Result<Self> {
+        let content = get_file_content(path)?;
+        // yaml is a superset of json, so one parser reads both
+        let policy = serde_yaml::from_slice(&content)
+            .map_err(|e| anyhow::anyhow!("{}: bad policy: {}", path, e))?;
+        Ok(policy)
+    }
+
+    fn char_classes(&self) -> CharClasses {
+        let allowed = |c: &char| !self.exclude.contains(*c);
+        let all: Vec<char> = match &self.alphabet {
+            Some(alphabet) => {
+                let mut all: Vec<char> = alphabet.chars().filter(allowed).collect();
+                all.sort_unstable();
+                all.dedup();
+                all
+            }
+            None => [
+                (self.uppercase, UPPER),
+                (self.lowercase, LOWER),
+                (self.number, NUMBER),
+                (self.symbols, SYMBOL),
+            ]
+            .iter()
+            .filter(|(enabled, _)| *enabled)
+            .flat_map(|(_, class)| class.iter().map(|&b| char::from(b)))
+            .filter(allowed)
+            .collect(),
+        };
+        let class = |is: fn(&char) -> bool| all.iter().copied().filter(is).collect::<Vec<_>>();
+        let upper = class(char::is_ascii_uppercase);
+        let lower = class(char::is_ascii_lowercase);
+        let number = class(char::is_ascii_digit);
+        let symbol = class(|c| !c.is_ascii_alphanumeric());
+        // a custom alphabet only asks for the classes it has
+        let min = |min: Option<usize>, enabled: bool, chars: &[char]| {
+            min.unwrap_or(usize::from(enabled && !chars.is_empty()))
+        };
+        let classes = [
+            (
+                "uppercase",
+                min(self.min_upper, self.uppercase, &upper),
+                upper,
+            ),
+            (
+                "lowercase",
+                min(self.min_lower, self.lowercase, &lower),
+                lower,
+            ),
+            ("number", min(self.min_number, self.number, &number), number),
+            (
+                "symbol",
+                min(self.min_symbol, self.symbols, &symbol),
+                symbol,
+            ),
+        ];
+        CharClasses { all, classes }
+    }
+
+    /// Explain why no password of `length` can follow the policy, if that's the case.
+    fn check(&self, length: usize, chars: &CharClasses) -> Result<()> {
+        if length == 0 {
+            bail!("the length must be at least 1");
+        }
+        let classes = [self.uppercase, self.lowercase, self.number, self.symbols];
+        if self.alphabet.is_none() && !classes.contains(&true) {
+            bail!("every character class is disabled, keep one or give an alphabet");
+        }
+        if chars.all.is_empty() {
+            bail!("the policy leaves no characters to choose from");
+        }
+        for (name, min, class) in &chars.classes {
+            if *min > 0 && class.is_empty() {
+                bail!(
+                    "the policy asks for {} {} characters but allows none",
+                    min,
+                    name
+                );
+            }
+        }
+        let required = chars
+            .classes
+            .iter()
+            .fold(0usize, |sum, (_, min, _)| sum.saturating_add(*min));
+        if required > length {
+            bail!(
+                "the policy needs at least {} characters but the length is {}",
+                required,
+                length
+            );
+        }
+        if self.start_with_letter && !chars.all.iter().any(|c| c.is_alphabetic()) {
+            bail!("the policy must start with a letter but allows none");
+        }
+        let not_letters = chars.classes[2].1.saturating_add(chars.classes[3].1);
+        if self.start_with_letter && not_letters >= length {
+            bail!(
+                "the policy must start with a letter but its {} digits and symbols fill the length",
+                not_letters
+            );
+        }
+        let Some(max) = self.max_repeat else {
+            return Ok(());
+        };
+        if max == 0 {
+            bail!("max_repeat must be at least 1");
+        }
+        if chars.all.len() == 1 && length > max {
+            bail!(
+                "only {:?} is allowed, which can't repeat {} times",
+                chars.all[0],
+                length
+            );
+        }
+        // a class of one character needs the other characters to break its runs up
+        for (name, min, class) in &chars.classes {
+            if let [c] = class.as_slice() {
+                // the runs go between the other characters, not before a required first letter
+                let gaps =
+                    length - min + 1 - usize::from(self.start_with_letter && !c.is_alphabetic());
+                if *min > max.saturating_mul(gaps) {
+                    bail!(
+                        "{} {:?} {} times can't be kept to runs of {} in {} characters",
+                        name,
+                        c,
+                        min,
+                        max,
+                        length
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Generate a password of `length` that follows `policy`, or explain why there is none.
+pub fn process_genpass(length: usize, policy: &PasswordPolicy) -> Result<String> {
+    let chars = policy.char_classes();
+    policy.check(length, &chars)?;
+    let letters: Vec<char> = chars
+        .all
+        .iter()
+        .copied()
+        .filter(|c| c.is_alphabetic())
+        .collect();
     let mut rng = rand::thread_rng();
-    let mut password: Vec<u8> = Vec::with_capacity(length as usize);
-    let mut chars = Vec::new();
-    if upper {
-        chars.extend_from_slice(UPPER);
-        password.push(*UPPER.choose(&mut rng).expect("UPPER won't be empty"));
+    'attempt: for _ in 0..MAX_ATTEMPTS {
+        // the class each position draws from, any allowed character where there's none
+        let mut slots: Vec<Option<usize>> = chars
+            .classes
+            .iter()
+            .enumerate()
+            .flat_map(|(class, (_, min, _))| std::iter::repeat_n(Some(class), *min))
+            .collect();
+        slots.resize(length, None);
+        slots.shuffle(&mut rng);
+        // uppercase and lowercase are the first two classes
+        let letter_slot = |slot: &Option<usize>| slot.is_none_or(|class| class < 2);
+        if policy.start_with_letter && !letter_slot(&slots[0]) {
+            let free: Vec<usize> = (1..length).filter(|&i| letter_slot(&slots[i])).collect();
+            let i = *free.choose(&mut rng).expect("checked in check");
+            slots.swap(0, i);
+        }
+        let pools: Vec<&[char]> = slots
+            .iter()
+            .enumerate()
+            .map(|(i, slot)| match slot {
+                Some(class) => chars.classes[*class].2.as_slice(),
+                None if i == 0 && policy.start_with_letter => letters.as_slice(),
+                None => chars.all.as_slice(),
+            })
+            .collect();
+        let max_repeat = policy.max_repeat.unwrap_or(usize::MAX);
+        let mut password: Vec<char> = Vec::with_capacity(length);
+        for i in 0..length {
+            // the run a character would make, counting the single character positions after it
+            let run = |c: char| {
+                let before = password.iter().rev().take_while(|&&p| p == c).count();
+                let after = pools[i + 1..]
+                    .iter()
+                    .take_while(|pool| **pool == [c])
+                    .count();
+                before + 1 + after
+            };
+            let allowed: Vec<char> = pools[i]
+                .iter()
+                .copied()
+                .filter(|&c| run(c) <= max_repeat)
+                .collect();
+            // only classes of a single character can corner a position, they're laid out again
+            let Some(&c) = allowed.choose(&mut rng) else {
+                continue 'attempt;
+            };
+            password.push(c);
+        }
+        return Ok(password.into_iter().collect());
     }
-    if lower {
-        chars.extend_from_slice(LOWER);
-        password.push(*LOWER.choose(&mut rng).expect("LOWER won't be empty"));
+    bail!(
+        "couldn't generate a password following the policy in {} tries",
+        MAX_ATTEMPTS
+    )
+}
+
+/// Bits of entropy of a password of `length` drawn from what `policy` allows.
+pub fn genpass_entropy(length: usize, policy: &PasswordPolicy) -> f64 {
+    length as f64 * (policy.char_classes().all.len() as f64).log2()
+}
+
+/// Call `generate` for `count` passwords, each drawn again until zxcvbn scores it at least
+/// `min_score`.
+pub fn process_genpass_batch(
+    count: usize,
+    min_score: u8,
+    mut generate: impl FnMut() -> Result<(String, f64)>,
+) -> Result<Vec<GeneratedPassword>> {
+    (0..count)
+        .map(|_| {
+            for _ in 0..MAX_ATTEMPTS {
+                let (password, entropy) = generate()?;
+                let score = zxcvbn(&password, &[])?.score();
+                if score >= min_score {
+                    return Ok(GeneratedPassword {
+                        password,
+                        entropy: (entropy * 10.0).round() / 10.0,
+                        score,
+                    });
+                }
+            }
+            bail!(
+                "no password reached score {} in {} tries, make them longer",
+                min_score,
+                MAX_ATTEMPTS
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn longest_run(password: &[char]) -> usize {
+        password
+            .chunk_by(|a, b| a == b)
+            .map(<[char]>::len)
+            .max()
+            .unwrap_or(0)
+    }
+
+    #[test]
+    fn test_policy_file() -> Result<()> {
+        let policy = PasswordPolicy::load("fixtures/password_policy.yaml")?;
+        for _ in 0..200 {
+            let password = process_genpass(policy.length.unwrap(), &policy)?;
+            assert_eq!(password.chars().count(), 12);
+            assert!(!password.contains('$'));
+            assert!(password.starts_with(|c: char| c.is_ascii_alphabetic()));
+            assert!(password.chars().filter(char::is_ascii_digit).count() >= 2);
+            let chars: Vec<char> = password.chars().collect();
+            assert!(longest_run(&chars) <= 3);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_custom_alphabet() -> Result<()> {
+        let policy = PasswordPolicy {
+            alphabet: Some("abc123".into()),
+            exclude: "3".into(),
+            min_number: Some(4),
+            ..Default::default()
+        };
+        let password = process_genpass(8, &policy)?;
+        assert!(password.chars().all(|c| "abc12".contains(c)));
+        assert!(password.chars().filter(char::is_ascii_digit).count() >= 4);
+        assert_eq!(genpass_entropy(8, &policy), 8.0 * 5f64.log2());
+        Ok(())
     }
-    if number {
-        chars.extend_from_slice(NUMBER);
-        password.push(*NUMBER.choose(&mut rng).expect("NUMBER won't be empty"));
+
+    #[test]
+    fn test_unsatisfiable_policies() {
+        let err = |policy: PasswordPolicy, length| {
+            process_genpass(length, &policy).unwrap_err().to_string()
+        };
+        let policy = PasswordPolicy {
+            min_number: Some(3),
+            ..Default::default()
+        };
+        assert_eq!(
+            err(policy, 4),
+            "the policy needs at least 6 characters but the length is 4"
+        );
+        let policy = PasswordPolicy {
+            alphabet: Some("0123456789".into()),
+            start_with_letter: true,
+            ..Default::default()
+        };
+        assert!(err(policy, 8).contains("start with a letter"));
+        let policy = PasswordPolicy {
+            alphabet: Some("ab".into()),
+            min_symbol: Some(1),
+            ..Default::default()
+        };
+        assert!(err(policy, 8).contains("1 symbol characters but allows none"));
+        let policy = PasswordPolicy {
+            alphabet: Some("aaa".into()),
+            max_repeat: Some(2),
+            ..Default::default()
+        };
+        assert!(err(policy, 3).contains("can't repeat"));
+        let policy = PasswordPolicy {
+            min_number: Some(usize::MAX),
+            min_symbol: Some(usize::MAX),
+            ..Default::default()
+        };
+        assert!(err(policy, 8).contains("needs at least"));
     }
-    if symbol {
-        chars.extend_from_slice(SYMBOL);
-        password.push(*SYMBOL.choose(&mut rng).expect("SYMBOL won't be empty"));
+
+    #[test]
+    fn test_repeat_limits_are_met() -> Result<()> {
+        let policy = PasswordPolicy {
+            max_repeat: Some(1),
+            ..Default::default()
+        };
+        let password: Vec<char> = process_genpass(500, &policy)?.chars().collect();
+        assert_eq!(password.len(), 500);
+        assert_eq!(longest_run(&password), 1);
+
+        let policy = PasswordPolicy {
+            alphabet: Some("ab".into()),
+            max_repeat: Some(1),
+            ..Default::default()
+        };
+        let password = process_genpass(40, &policy)?;
+        assert!(password == "ab".repeat(20) || password == "ba".repeat(20));
+
+        let policy = PasswordPolicy {
+            alphabet: Some("a1".into()),
+            min_number: Some(3),
+            max_repeat: Some(1),
+            start_with_letter: true,
+            ..Default::default()
+        };
+        assert_eq!(process_genpass(6, &policy)?, "a1a1a1");
+        assert!(process_genpass(5, &policy)
+            .unwrap_err()
+            .to_string()
+            .contains("can't be kept to runs of 1"));
+
+        // a limit beyond the length is no limit
+        let policy = PasswordPolicy {
+            alphabet: Some("a1".into()),
+            min_number: Some(3),
+            max_repeat: Some(usize::MAX),
+            ..Default::default()
+        };
+        assert_eq!(process_genpass(6, &policy)?.len(), 6);
+        Ok(())
     }
-    for _ in 0..length - password.len() as u8 {
-        let c = *chars.choose(&mut rng).expect("chars won't be empty");
 
-        password.push(c);
+    #[test]
+    fn test_genpass_batch() -> Result<()> {
+        let policy = PasswordPolicy::default();
+        let passwords = process_genpass_batch(5, 4, || {
+            Ok((process_genpass(16, &policy)?, genpass_entropy(16, &policy)))
+        })?;
+        assert_eq!(passwords.len(), 5);
+        assert!(passwords
+            .iter()
+            .all(|p| p.score == 4 && p.password.len() == 16));
+        assert_eq!(passwords[0].entropy, 96.7);
+
+        let weak = PasswordPolicy {
+            alphabet: Some("ab".into()),
+            ..Default::default()
+        };
+        let err = process_genpass_batch(1, 3, || Ok((process_genpass(4, &weak)?, 4.0)));
+        assert!(err
+            .unwrap_err()
+            .to_string()
+            .contains("no password reached score 3"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_disabled_classes_and_lengths() -> Result<()> {
+        let policy = PasswordPolicy {
+            symbols: false,
+            number: false,
+            ..Default::default()
+        };
+        for _ in 0..50 {
+            let password = process_genpass(20, &policy)?;
+            assert!(password.chars().all(|c| c.is_ascii_alphabetic()));
+        }
+        assert_eq!(process_genpass(1000, &policy)?.len(), 1000);
+        // one character can't hold one of each of the four classes
+        assert!(process_genpass(1, &PasswordPolicy::default()).is_err());
+        assert!(process_genpass(0, &policy).is_err());
+
+        let none = PasswordPolicy {
+            uppercase: false,
+            lowercase: false,
+            number: false,
+            symbols: false,
+            ..Default::default()
+        };
+        let err = process_genpass(16, &none).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("every character class is disabled"));
+        let excluded = PasswordPolicy {
+            alphabet: Some("ab".into()),
+            exclude: "ab".into(),
+            ..Default::default()
+        };
+        assert!(process_genpass(16, &excluded).is_err());
+        let only_digits = PasswordPolicy {
+            number: true,
+            ..none
+        };
+        assert!(process_genpass(4, &only_digits)?
+            .chars()
+            .all(|c| c.is_ascii_digit()));
+        Ok(())
     }
-    password.shuffle(&mut rng);
-    let password = String::from_utf8(password).unwrap();
-    Ok(password)
 }
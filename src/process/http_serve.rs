@@ -79,6 +79,10 @@ async fn execute(serve_path: &PathBuf, req_path: Option<&str>) -> (StatusCode, R
         }
     } else {
         match tokio::fs::read_to_string(&p).await {
+            // html pages, e.g. from `rcli csv --format html`, are rendered rather than shown as text
+            Ok(content) if p.extension().is_some_and(|ext| ext == "html") => {
+                (StatusCode::OK, Html(content).into_response())
+            }
             Ok(content) => (StatusCode::OK, content.into_response()),
             Err(e) => {
                 warn!("Error reading file {:?} : {:?}", p.display(), e);
@@ -1,16 +1,46 @@
 pub mod b64;
 pub mod csv_convert;
+pub mod csv_diff;
+pub mod csv_fake;
+pub mod csv_filter;
+pub mod csv_nested;
+pub mod csv_ops;
+pub mod csv_parallel;
+pub mod csv_query;
+pub mod csv_rejects;
+pub mod csv_sniff;
+pub mod csv_stats;
+pub mod csv_types;
+pub mod csv_validate;
 pub mod gen_pass;
 pub mod http_serve;
 pub mod json_web_token;
+pub mod pass_check;
+pub mod passphrase;
+pub mod row_writer;
+pub mod table;
 pub mod text;
 
 pub use b64::*;
 pub use csv_convert::*;
+pub use csv_diff::*;
+pub use csv_fake::*;
+pub use csv_filter::*;
+pub use csv_nested::*;
+pub use csv_ops::*;
+pub use csv_query::*;
+pub use csv_rejects::*;
+pub use csv_sniff::*;
+pub use csv_stats::*;
+pub use csv_validate::*;
 pub use gen_pass::*;
 pub use http_serve::*;
 pub use json_web_token::*;
+pub use pass_check::*;
+pub use passphrase::*;
+pub use row_writer::*;
 use std::io::Read;
+pub use table::*;
 pub use text::*;
 
 pub trait TextSign {
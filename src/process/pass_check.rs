@@ -0,0 +1,183 @@
+use crate::utils::get_reader;
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::io::{BufRead, BufReader, IsTerminal};
+use zxcvbn::{matching::patterns::MatchPattern, zxcvbn};
+
+/// What zxcvbn makes of one password. Passwords and matched tokens are left out, a
+/// report is safe to print or keep.
+#[derive(Debug, Serialize)]
+pub struct PasswordReport {
+    /// 1-based line of the password in the input
+    pub line: usize,
+    /// 0 (too guessable) to 4 (very unguessable)
+    pub score: u8,
+    pub guesses: u64,
+    pub guesses_log10: f64,
+    pub crack_times: CrackTimes,
+    pub warning: Option<String>,
+    pub suggestions: Vec<String>,
+    pub patterns: Vec<PatternReport>,
+}
+
+/// Estimated time to crack, as zxcvbn words it, e.g. "3 hours".
+#[derive(Debug, Serialize)]
+pub struct CrackTimes {
+    /// an online attack limited to 100 guesses an hour
+    pub online_throttled: String,
+    /// an online attack at 10 guesses a second
+    pub online: String,
+    /// an offline attack on a slow hash at 1e4 guesses a second
+    pub offline_slow_hash: String,
+    /// an offline attack on a fast hash at 1e10 guesses a second
+    pub offline_fast_hash: String,
+}
+
+/// One piece of the password zxcvbn recognized, by position rather than content.
+#[derive(Debug, Serialize)]
+pub struct PatternReport {
+    /// dictionary, spatial, repeat, sequence, regex, date or bruteforce
+    pub pattern: &'static str,
+    /// which dictionary, keyboard, sequence or regex matched
+    pub detail: Option<String>,
+    /// first and last character of the match, 0-based and inclusive
+    pub start: usize,
+    pub end: usize,
+    pub guesses: Option<u64>,
+}
+
+/// Read one password per line, blank lines are skipped but still counted. A terminal on
+/// stdin is asked for passwords without echo until an empty one.
+pub fn read_passwords(input: &str) -> Result<Vec<(usize, String)>> {
+    if input == "-" && std::io::stdin().is_terminal() {
+        let mut passwords = Vec::new();
+        loop {
+            let password = rpassword::prompt_password("password (empty to finish): ")?;
+            if password.is_empty() {
+                return Ok(passwords);
+            }
+            passwords.push((passwords.len() + 1, password));
+        }
+    }
+    let reader = BufReader::new(get_reader(input)?);
+    let mut passwords = Vec::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        let password = line.strip_suffix('\r').unwrap_or(&line);
+        if !password.is_empty() {
+            passwords.push((i + 1, password.to_string()));
+        }
+    }
+    Ok(passwords)
+}
+
+/// Run zxcvbn over each password, `user_inputs` like usernames count as dictionary words.
+pub fn process_genpass_check(
+    passwords: &[(usize, String)],
+    user_inputs: &[String],
+) -> Result<Vec<PasswordReport>> {
+    let user_inputs: Vec<&str> = user_inputs.iter().map(String::as_str).collect();
+    passwords
+        .iter()
+        .map(|(line, password)| {
+            let entropy =
+                zxcvbn(password, &user_inputs).map_err(|e| anyhow!("line {}: {}", line, e))?;
+            let times = entropy.crack_times();
+            let feedback = entropy.feedback().as_ref();
+            Ok(PasswordReport {
+                line: *line,
+                score: entropy.score(),
+                guesses: entropy.guesses(),
+                guesses_log10: entropy.guesses_log10(),
+                crack_times: CrackTimes {
+                    online_throttled: times.online_throttling_100_per_hour().to_string(),
+                    online: times.online_no_throttling_10_per_second().to_string(),
+                    offline_slow_hash: times.offline_slow_hashing_1e4_per_second().to_string(),
+                    offline_fast_hash: times.offline_fast_hashing_1e10_per_second().to_string(),
+                },
+                warning: feedback.and_then(|f| f.warning()).map(|w| w.to_string()),
+                suggestions: feedback
+                    .map(|f| f.suggestions().iter().map(ToString::to_string).collect())
+                    .unwrap_or_default(),
+                patterns: entropy
+                    .sequence()
+                    .iter()
+                    .map(|m| {
+                        let (pattern, detail) = describe(&m.pattern);
+                        PatternReport {
+                            pattern,
+                            detail,
+                            start: m.i,
+                            end: m.j,
+                            guesses: m.guesses,
+                        }
+                    })
+                    .collect(),
+            })
+        })
+        .collect()
+}
+
+/// The kind of a match and what it matched against, never the matched text.
+fn describe(pattern: &MatchPattern) -> (&'static str, Option<String>) {
+    match pattern {
+        MatchPattern::Dictionary(d) => {
+            let mut detail = snake_case(&format!("{:?}", d.dictionary_name));
+            if d.l33t {
+                detail.push_str(", l33t");
+            }
+            if d.reversed {
+                detail.push_str(", reversed");
+            }
+            ("dictionary", Some(detail))
+        }
+        MatchPattern::Spatial(s) => ("spatial", Some(s.graph.clone())),
+        MatchPattern::Repeat(r) => ("repeat", Some(format!("{} times", r.repeat_count))),
+        MatchPattern::Sequence(s) => ("sequence", Some(s.sequence_name.to_string())),
+        MatchPattern::Regex(r) => ("regex", Some(r.regex_name.to_string())),
+        MatchPattern::Date(_) => ("date", None),
+        MatchPattern::BruteForce => ("bruteforce", None),
+    }
+}
+
+/// `UsTvAndFilm` to `us_tv_and_film`.
+fn snake_case(name: &str) -> String {
+    let mut snake = String::with_capacity(name.len() + 4);
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            snake.push('_');
+        }
+        snake.extend(c.to_lowercase());
+    }
+    snake
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_genpass_check() -> Result<()> {
+        let passwords = read_passwords("fixtures/passwords.txt")?;
+        let lines: Vec<usize> = passwords.iter().map(|(line, _)| *line).collect();
+        assert_eq!(lines, [1, 2, 4]);
+
+        let reports = process_genpass_check(&passwords, &["tyrion".into()])?;
+        assert!(reports[0].score <= 1);
+        assert!(reports[0].warning.is_some());
+        assert_eq!(reports[0].patterns[0].pattern, "dictionary");
+        assert_eq!(reports[0].patterns[0].detail.as_deref(), Some("passwords"));
+        // the user input is what gives the second one away
+        let user = &reports[1].patterns[0];
+        assert_eq!(user.detail.as_deref(), Some("user_inputs"));
+        assert_eq!((user.start, user.end), (0, 5));
+        assert_eq!(reports[2].score, 4);
+        assert!(reports[2].guesses_log10 > 10.0);
+
+        let report = serde_json::to_string(&reports)?;
+        for (_, password) in &passwords {
+            assert!(!report.contains(password.as_str()));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,754 @@
+use crate::cli::OutputFormat;
+use crate::process::csv_filter::cell_text;
+use crate::process::table::{render_html, render_markdown, render_table, truncate_cell};
+use crate::utils::get_file_content;
+use anyhow::{anyhow, bail, Result};
+use csv::Writer;
+use serde_json::{Map, Value};
+use std::{
+    collections::{HashMap, HashSet},
+    io::Write,
+};
+
+/// Sink for converted rows, rows are written as they come in where the format allows it.
+pub trait RowWriter {
+    fn write_row(&mut self, row: &Value) -> Result<()>;
+    /// Write a row already turned into text by [`encode_row`].
+    fn write_encoded(&mut self, _encoded: &str) -> Result<()> {
+        bail!("this output format can't take encoded rows")
+    }
+    fn finish(self: Box<Self>) -> Result<()>;
+}
+
+/// Encode a single row the way its writer would, so encoding can happen on another thread.
+/// `None` for formats and shapes that don't encode rows on their own.
+pub fn encode_row(
+    format: OutputFormat,
+    opts: &WriterOptions,
+    row: &Value,
+) -> Option<Result<String>> {
+    if opts.shape != OutputShape::Rows {
+        return None;
+    }
+    let encoded = match format {
+        OutputFormat::Json => encode_json_item(row),
+        OutputFormat::Jsonl => serde_json::to_string(row).map_err(Into::into),
+        OutputFormat::Yaml => serde_yaml::to_string(std::slice::from_ref(row)).map_err(Into::into),
+        _ => return None,
+    };
+    Some(encoded)
+}
+
+/// A pretty printed row indented as an element of the top level array.
+fn encode_json_item(row: &Value) -> Result<String> {
+    // json strings never hold a raw newline, so indenting every line is safe
+    let pretty = serde_json::to_string_pretty(row)?;
+    let lines: Vec<String> = pretty.lines().map(|line| format!("  {}", line)).collect();
+    Ok(lines.join("\n"))
+}
+
+#[derive(Debug, Clone)]
+pub struct WriterOptions {
+    /// key of the array of tables in toml output
+    pub toml_key: String,
+    pub shape: OutputShape,
+    /// widest cell of table output in terminal columns, 0 for no limit
+    pub max_width: usize,
+    /// html page with a `{{content}}` placeholder for the table, a plain page when unset
+    pub template: Option<String>,
+    /// columns known before any row arrives, csv writes them even when no row follows
+    pub headers: Vec<String>,
+}
+
+/// Page html output is dropped into, in the `{{content}}` style of the http serve listing.
+const HTML_TEMPLATE: &str = include_str!("../../fixtures/table.html");
+
+/// How rows are laid out in json/yaml output.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum OutputShape {
+    /// an array of rows
+    #[default]
+    Rows,
+    /// an object of rows keyed by the value of a column, which must be unique
+    KeyBy(String),
+    /// an object of arrays of rows sharing the value of a column
+    GroupBy(String),
+}
+
+impl Default for WriterOptions {
+    fn default() -> Self {
+        Self {
+            toml_key: "rows".into(),
+            shape: OutputShape::Rows,
+            max_width: 40,
+            template: None,
+            headers: Vec::new(),
+        }
+    }
+}
+
+impl WriterOptions {
+    /// Make sure the options make sense for `format`.
+    pub fn check(&self, format: OutputFormat) -> Result<()> {
+        if self.shape != OutputShape::Rows
+            && !matches!(format, OutputFormat::Json | OutputFormat::Yaml)
+        {
+            bail!(
+                "--key-by and --group-by need json or yaml output, not {}",
+                format
+            );
+        }
+        if self.template.is_some() && !matches!(format, OutputFormat::Html) {
+            bail!("--template needs html output, not {}", format);
+        }
+        Ok(())
+    }
+
+    fn html_template(&self) -> Result<String> {
+        let Some(path) = &self.template else {
+            return Ok(HTML_TEMPLATE.to_string());
+        };
+        let tmpl = String::from_utf8(get_file_content(path)?)?;
+        if !tmpl.contains("{{content}}") {
+            bail!("{}: template has no {{{{content}}}} placeholder", path);
+        }
+        Ok(tmpl)
+    }
+}
+
+pub fn row_writer<'a>(
+    format: OutputFormat,
+    output: Box<dyn Write + 'a>,
+    opts: &WriterOptions,
+) -> Box<dyn RowWriter + 'a> {
+    if opts.shape != OutputShape::Rows {
+        return Box::new(ShapedWriter::new(output, format, opts.shape.clone()));
+    }
+    match format {
+        OutputFormat::Json => Box::new(JsonArrayWriter::new(output)),
+        OutputFormat::Jsonl => Box::new(JsonLinesWriter { output }),
+        OutputFormat::Yaml => Box::new(YamlWriter::new(output)),
+        OutputFormat::Csv => Box::new(CsvRowWriter::new(output, opts.headers.clone())),
+        OutputFormat::Toml => Box::new(TomlWriter::new(output, opts.toml_key.clone())),
+        OutputFormat::Msgpack => Box::new(MsgpackWriter::new(output)),
+        OutputFormat::Cbor => Box::new(CborWriter::new(output)),
+        OutputFormat::Markdown | OutputFormat::Html | OutputFormat::Table => {
+            Box::new(GridWriter::new(output, format, opts.clone()))
+        }
+    }
+}
+
+/// Pretty printed json array, written one element at a time.
+struct JsonArrayWriter<'a> {
+    output: Box<dyn Write + 'a>,
+    count: usize,
+}
+
+struct JsonLinesWriter<'a> {
+    output: Box<dyn Write + 'a>,
+}
+
+struct YamlWriter<'a> {
+    output: Box<dyn Write + 'a>,
+    count: usize,
+}
+
+/// Csv needs the union of all keys up front, so rows are buffered until `finish`.
+struct CsvRowWriter<'a> {
+    output: Box<dyn Write + 'a>,
+    headers: Vec<String>,
+    rows: Vec<Value>,
+}
+
+/// Toml has no top level array, rows become an array of tables under `key`.
+struct TomlWriter<'a> {
+    output: Box<dyn Write + 'a>,
+    key: String,
+    rows: Vec<Value>,
+}
+
+/// Msgpack arrays carry their length up front, so rows are buffered until `finish`.
+struct MsgpackWriter<'a> {
+    output: Box<dyn Write + 'a>,
+    rows: Vec<Value>,
+}
+
+/// Cbor allows arrays of unknown length, so rows are written as they come in.
+struct CborWriter<'a> {
+    output: Box<dyn Write + 'a>,
+    started: bool,
+}
+
+/// Markdown, html and terminal tables size their columns to every row, so rows are
+/// buffered until `finish`.
+struct GridWriter<'a> {
+    output: Box<dyn Write + 'a>,
+    format: OutputFormat,
+    opts: WriterOptions,
+    rows: Vec<Value>,
+}
+
+/// Rows keyed or grouped by a column, the object is only complete after the last row.
+struct ShapedWriter<'a> {
+    output: Box<dyn Write + 'a>,
+    format: OutputFormat,
+    shape: OutputShape,
+    doc: Map<String, Value>,
+    count: usize,
+    /// row each key was first seen in, to report duplicates
+    first_seen: HashMap<String, usize>,
+}
+
+impl<'a> JsonArrayWriter<'a> {
+    fn new(output: Box<dyn Write + 'a>) -> Self {
+        Self { output, count: 0 }
+    }
+}
+
+impl RowWriter for JsonArrayWriter<'_> {
+    fn write_row(&mut self, row: &Value) -> Result<()> {
+        self.write_encoded(&encode_json_item(row)?)
+    }
+
+    fn write_encoded(&mut self, encoded: &str) -> Result<()> {
+        let sep = if self.count == 0 { "[\n" } else { ",\n" };
+        self.output.write_all(sep.as_bytes())?;
+        self.output.write_all(encoded.as_bytes())?;
+        self.count += 1;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        let end = if self.count == 0 { "[]\n" } else { "\n]\n" };
+        self.output.write_all(end.as_bytes())?;
+        self.output.flush()?;
+        Ok(())
+    }
+}
+
+impl RowWriter for JsonLinesWriter<'_> {
+    fn write_row(&mut self, row: &Value) -> Result<()> {
+        serde_json::to_writer(&mut self.output, row)?;
+        self.output.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn write_encoded(&mut self, encoded: &str) -> Result<()> {
+        self.output.write_all(encoded.as_bytes())?;
+        self.output.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        self.output.flush()?;
+        Ok(())
+    }
+}
+
+impl<'a> YamlWriter<'a> {
+    fn new(output: Box<dyn Write + 'a>) -> Self {
+        Self { output, count: 0 }
+    }
+}
+
+impl RowWriter for YamlWriter<'_> {
+    fn write_row(&mut self, row: &Value) -> Result<()> {
+        // a one element sequence renders exactly as one item of the full sequence
+        let item = serde_yaml::to_string(std::slice::from_ref(row))?;
+        self.write_encoded(&item)
+    }
+
+    fn write_encoded(&mut self, encoded: &str) -> Result<()> {
+        self.output.write_all(encoded.as_bytes())?;
+        self.count += 1;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        if self.count == 0 {
+            self.output.write_all(b"[]\n")?;
+        }
+        self.output.flush()?;
+        Ok(())
+    }
+}
+
+impl<'a> CsvRowWriter<'a> {
+    fn new(output: Box<dyn Write + 'a>, headers: Vec<String>) -> Self {
+        Self {
+            output,
+            headers,
+            rows: Vec::with_capacity(128),
+        }
+    }
+}
+
+impl RowWriter for CsvRowWriter<'_> {
+    fn write_row(&mut self, row: &Value) -> Result<()> {
+        self.rows.push(row.clone());
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> Result<()> {
+        let mut writer = Writer::from_writer(self.output);
+        write_csv_rows(&mut writer, &self.headers, &self.rows)?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+impl<'a> TomlWriter<'a> {
+    fn new(output: Box<dyn Write + 'a>, key: String) -> Self {
+        Self {
+            output,
+            key,
+            rows: Vec::with_capacity(128),
+        }
+    }
+}
+
+impl RowWriter for TomlWriter<'_> {
+    fn write_row(&mut self, row: &Value) -> Result<()> {
+        let row = strip_nulls(row.clone())
+            .ok_or_else(|| anyhow!("row {}: toml can't hold a null row", self.rows.len() + 1))?;
+        self.rows.push(row);
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        let mut doc = serde_json::Map::new();
+        doc.insert(
+            self.key.clone(),
+            Value::Array(std::mem::take(&mut self.rows)),
+        );
+        let content = toml::to_string(&doc)?;
+        self.output.write_all(content.as_bytes())?;
+        self.output.flush()?;
+        Ok(())
+    }
+}
+
+/// Toml has no null, so null cells are left out of their table.
+fn strip_nulls(value: Value) -> Option<Value> {
+    match value {
+        Value::Null => None,
+        Value::Object(obj) => Some(Value::Object(
+            obj.into_iter()
+                .filter_map(|(k, v)| strip_nulls(v).map(|v| (k, v)))
+                .collect(),
+        )),
+        Value::Array(items) => Some(Value::Array(
+            items.into_iter().filter_map(strip_nulls).collect(),
+        )),
+        value => Some(value),
+    }
+}
+
+impl<'a> ShapedWriter<'a> {
+    fn new(output: Box<dyn Write + 'a>, format: OutputFormat, shape: OutputShape) -> Self {
+        Self {
+            output,
+            format,
+            shape,
+            doc: Map::new(),
+            count: 0,
+            first_seen: HashMap::new(),
+        }
+    }
+}
+
+impl RowWriter for ShapedWriter<'_> {
+    fn write_row(&mut self, row: &Value) -> Result<()> {
+        self.count += 1;
+        let column = match &self.shape {
+            OutputShape::KeyBy(column) | OutputShape::GroupBy(column) => column,
+            OutputShape::Rows => unreachable!("plain rows don't need a shaped writer"),
+        };
+        let key = row
+            .as_object()
+            .and_then(|obj| obj.get(column))
+            .map(cell_text)
+            .ok_or_else(|| anyhow!("row {}: no column {:?} to key by", self.count, column))?;
+        match &self.shape {
+            OutputShape::GroupBy(_) => {
+                let group = self.doc.entry(key).or_insert_with(|| Value::Array(vec![]));
+                if let Value::Array(rows) = group {
+                    rows.push(row.clone());
+                }
+            }
+            _ => {
+                if let Some(first) = self.first_seen.insert(key.clone(), self.count) {
+                    bail!(
+                        "row {}: duplicate key {:?} in column {:?}, first seen in row {}",
+                        self.count,
+                        key,
+                        column,
+                        first
+                    );
+                }
+                self.doc.insert(key, row.clone());
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        let doc = Value::Object(std::mem::take(&mut self.doc));
+        match self.format {
+            OutputFormat::Yaml => serde_yaml::to_writer(&mut self.output, &doc)?,
+            _ => {
+                serde_json::to_writer_pretty(&mut self.output, &doc)?;
+                self.output.write_all(b"\n")?;
+            }
+        }
+        self.output.flush()?;
+        Ok(())
+    }
+}
+
+impl<'a> GridWriter<'a> {
+    fn new(output: Box<dyn Write + 'a>, format: OutputFormat, opts: WriterOptions) -> Self {
+        Self {
+            output,
+            format,
+            opts,
+            rows: Vec::with_capacity(128),
+        }
+    }
+}
+
+impl RowWriter for GridWriter<'_> {
+    fn write_row(&mut self, row: &Value) -> Result<()> {
+        self.rows.push(row.clone());
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        let (headers, rows) = grid_cells(&self.opts.headers, &self.rows)?;
+        let content = match self.format {
+            OutputFormat::Markdown => render_markdown(&headers, &rows),
+            OutputFormat::Html => {
+                let table = render_html(&headers, &rows);
+                let page = self.opts.html_template()?.replace("{{content}}", &table);
+                format!("{}\n", page.trim_end())
+            }
+            _ => {
+                let max_width = self.opts.max_width;
+                let headers: Vec<String> = headers
+                    .iter()
+                    .map(|h| truncate_cell(h, max_width))
+                    .collect();
+                let rows: Vec<Vec<String>> = rows
+                    .iter()
+                    .map(|row| row.iter().map(|c| truncate_cell(c, max_width)).collect())
+                    .collect();
+                render_table(&headers, &rows)
+            }
+        };
+        self.output.write_all(content.as_bytes())?;
+        self.output.flush()?;
+        Ok(())
+    }
+}
+
+/// Rows as text cells under the `known` headers followed by the rest of their keys, nested
+/// values are shown as compact json.
+fn grid_cells(known: &[String], rows: &[Value]) -> Result<(Vec<String>, Vec<Vec<String>>)> {
+    let mut headers: Vec<String> = Vec::new();
+    let mut seen = HashSet::new();
+    for key in known {
+        if seen.insert(key.as_str()) {
+            headers.push(key.clone());
+        }
+    }
+    for (i, row) in rows.iter().enumerate() {
+        let obj = row
+            .as_object()
+            .ok_or_else(|| anyhow!("row {}: expected an object, found {}", i + 1, row))?;
+        for key in obj.keys() {
+            if seen.insert(key.as_str()) {
+                headers.push(key.clone());
+            }
+        }
+    }
+    let cells = rows
+        .iter()
+        .map(|row| headers.iter().map(|key| cell_text(&row[key])).collect())
+        .collect();
+    Ok((headers, cells))
+}
+
+impl<'a> MsgpackWriter<'a> {
+    fn new(output: Box<dyn Write + 'a>) -> Self {
+        Self {
+            output,
+            rows: Vec::with_capacity(128),
+        }
+    }
+}
+
+impl RowWriter for MsgpackWriter<'_> {
+    fn write_row(&mut self, row: &Value) -> Result<()> {
+        self.rows.push(row.clone());
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        // maps keep their keys (write_named) so the output reads back as objects
+        rmp_serde::encode::write_named(&mut self.output, &self.rows)?;
+        self.output.flush()?;
+        Ok(())
+    }
+}
+
+impl<'a> CborWriter<'a> {
+    fn new(output: Box<dyn Write + 'a>) -> Self {
+        Self {
+            output,
+            started: false,
+        }
+    }
+}
+
+const CBOR_INDEFINITE_ARRAY: u8 = 0x9f;
+const CBOR_BREAK: u8 = 0xff;
+
+impl RowWriter for CborWriter<'_> {
+    fn write_row(&mut self, row: &Value) -> Result<()> {
+        if !self.started {
+            self.output.write_all(&[CBOR_INDEFINITE_ARRAY])?;
+            self.started = true;
+        }
+        ciborium::into_writer(row, &mut self.output)?;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        if !self.started {
+            self.output.write_all(&[CBOR_INDEFINITE_ARRAY])?;
+        }
+        self.output.write_all(&[CBOR_BREAK])?;
+        self.output.flush()?;
+        Ok(())
+    }
+}
+
+/// Write rows as csv, the header is `known` followed by any other object keys in first-seen
+/// order. Without rows or known columns nothing is written.
+pub fn write_csv_rows<W: Write>(
+    writer: &mut Writer<W>,
+    known: &[String],
+    rows: &[Value],
+) -> Result<()> {
+    let mut seen = HashSet::new();
+    let mut headers: Vec<&str> = known
+        .iter()
+        .map(String::as_str)
+        .filter(|column| seen.insert(*column))
+        .collect();
+    for (i, row) in rows.iter().enumerate() {
+        let obj = row
+            .as_object()
+            .ok_or_else(|| anyhow!("row {}: expected an object, found {}", i + 1, row))?;
+        for (key, value) in obj {
+            if value.is_array() || value.is_object() {
+                bail!(
+                    "row {}, column {:?}: nested arrays/objects can't be written as a csv cell, try --flatten",
+                    i + 1,
+                    key
+                );
+            }
+            if seen.insert(key.as_str()) {
+                headers.push(key);
+            }
+        }
+    }
+
+    if headers.is_empty() {
+        return Ok(());
+    }
+    writer.write_record(&headers)?;
+    for row in rows {
+        let record = headers.iter().map(|key| match row.get(key) {
+            None | Some(Value::Null) => String::new(),
+            Some(Value::String(s)) => s.clone(),
+            Some(v) => v.to_string(),
+        });
+        writer.write_record(record)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn write_bytes(format: OutputFormat, rows: &[Value]) -> Result<Vec<u8>> {
+        write_shaped(format, OutputShape::Rows, rows)
+    }
+
+    fn write_shaped(format: OutputFormat, shape: OutputShape, rows: &[Value]) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        let opts = WriterOptions {
+            shape,
+            ..Default::default()
+        };
+        let mut writer = row_writer(format, Box::new(&mut buf), &opts);
+        for row in rows {
+            writer.write_row(row)?;
+        }
+        writer.finish()?;
+        Ok(buf)
+    }
+
+    fn write_all(format: OutputFormat, rows: &[Value]) -> Result<String> {
+        Ok(String::from_utf8(write_bytes(format, rows)?)?)
+    }
+
+    #[test]
+    fn test_streamed_output_matches_batch() -> Result<()> {
+        let rows = [
+            json!({"Name": "Buffon", "Kit Number": 77, "Clubs": ["Parma", "Juventus"]}),
+            json!({"Name": "Perin", "Kit Number": null, "Clubs": []}),
+        ];
+        for rows in [&rows[..], &[]] {
+            assert_eq!(
+                write_all(OutputFormat::Json, rows)?,
+                format!("{}\n", serde_json::to_string_pretty(rows)?)
+            );
+            assert_eq!(
+                write_all(OutputFormat::Yaml, rows)?,
+                serde_yaml::to_string(rows)?
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_encoded_rows_match_written_rows() -> Result<()> {
+        let rows = [
+            json!({"Name": "Buffon", "Clubs": ["Parma", "Juventus"]}),
+            json!({"Name": "Perin", "Kit Number": null}),
+        ];
+        let opts = WriterOptions::default();
+        for format in [OutputFormat::Json, OutputFormat::Jsonl, OutputFormat::Yaml] {
+            let mut buf = Vec::new();
+            let mut writer = row_writer(format, Box::new(&mut buf), &opts);
+            for row in &rows {
+                writer.write_encoded(&encode_row(format, &opts, row).unwrap()?)?;
+            }
+            writer.finish()?;
+            assert_eq!(String::from_utf8(buf)?, write_all(format, &rows)?);
+        }
+        assert!(encode_row(OutputFormat::Csv, &opts, &rows[0]).is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_known_headers() -> Result<()> {
+        let write_format = |format, headers: &[&str], rows: &[Value]| -> Result<String> {
+            let mut buf = Vec::new();
+            let opts = WriterOptions {
+                headers: headers.iter().map(|h| h.to_string()).collect(),
+                ..Default::default()
+            };
+            let mut writer = row_writer(format, Box::new(&mut buf), &opts);
+            for row in rows {
+                writer.write_row(row)?;
+            }
+            writer.finish()?;
+            Ok(String::from_utf8(buf)?)
+        };
+        let write =
+            |headers: &[&str], rows: &[Value]| write_format(OutputFormat::Csv, headers, rows);
+        assert_eq!(write(&["Name", "Position"], &[])?, "Name,Position\n");
+        assert_eq!(write(&[], &[])?, "");
+        assert_eq!(
+            write(
+                &["Position"],
+                &[json!({"Name": "Buffon", "Position": "Goalkeeper"})]
+            )?,
+            "Position,Name\nGoalkeeper,Buffon\n"
+        );
+
+        let markdown = write_format(OutputFormat::Markdown, &["Name", "Position"], &[])?;
+        assert!(markdown.starts_with("| Name | Position |"), "{}", markdown);
+        let table = write_format(OutputFormat::Table, &["Name", "Position"], &[])?;
+        assert!(table.starts_with("Name"), "{}", table);
+        let html = write_format(OutputFormat::Html, &["Name"], &[])?;
+        assert!(html.contains("<th>Name</th>"), "{}", html);
+        Ok(())
+    }
+
+    #[test]
+    fn test_json_lines() -> Result<()> {
+        let rows = [json!({"Name": "Buffon"}), json!({"Name": "Perin"})];
+        assert_eq!(
+            write_all(OutputFormat::Jsonl, &rows)?,
+            "{\"Name\":\"Buffon\"}\n{\"Name\":\"Perin\"}\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_toml_array_of_tables() -> Result<()> {
+        let rows = [
+            json!({"Name": "Buffon", "Kit Number": 77}),
+            json!({"Name": "Perin", "Kit Number": null}),
+        ];
+        assert_eq!(
+            write_all(OutputFormat::Toml, &rows)?,
+            "[[rows]]\nName = \"Buffon\"\n\"Kit Number\" = 77\n\n[[rows]]\nName = \"Perin\"\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_key_by_and_group_by() -> Result<()> {
+        let rows = [
+            json!({"Name": "Buffon", "Position": "Goalkeeper"}),
+            json!({"Name": "Perin", "Position": "Goalkeeper"}),
+            json!({"Name": "Dybala", "Position": "Forward"}),
+        ];
+        let keyed = write_shaped(OutputFormat::Json, OutputShape::KeyBy("Name".into()), &rows)?;
+        let keyed: Value = serde_json::from_slice(&keyed)?;
+        assert_eq!(keyed["Perin"], rows[1]);
+
+        let grouped = write_shaped(
+            OutputFormat::Yaml,
+            OutputShape::GroupBy("Position".into()),
+            &rows,
+        )?;
+        let grouped: Value = serde_yaml::from_slice(&grouped)?;
+        assert_eq!(grouped["Goalkeeper"], json!([rows[0], rows[1]]));
+        assert_eq!(grouped["Forward"], json!([rows[2]]));
+
+        let err = write_shaped(
+            OutputFormat::Json,
+            OutputShape::KeyBy("Position".into()),
+            &rows,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "row 2: duplicate key \"Goalkeeper\" in column \"Position\", first seen in row 1"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_binary_formats_round_trip() -> Result<()> {
+        let rows = vec![
+            json!({"Name": "Buffon", "Kit Number": 77, "Active": true}),
+            json!({"Name": "Perin", "Kit Number": null, "Active": false}),
+        ];
+        let msgpack = write_bytes(OutputFormat::Msgpack, &rows)?;
+        assert_eq!(rmp_serde::from_slice::<Vec<Value>>(&msgpack)?, rows);
+        let cbor = write_bytes(OutputFormat::Cbor, &rows)?;
+        assert_eq!(ciborium::from_reader::<Vec<Value>, _>(&cbor[..])?, rows);
+        let cbor = write_bytes(OutputFormat::Cbor, &[])?;
+        assert!(ciborium::from_reader::<Vec<Value>, _>(&cbor[..])?.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,193 @@
+use unicode_width::UnicodeWidthStr;
+
+const ELLIPSIS: char = '…';
+
+/// Render rows as a plain text table with left aligned, padded columns.
+pub fn render_table(headers: &[String], rows: &[Vec<String>]) -> String {
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.width()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.width());
+        }
+    }
+    let line = |cells: &[String]| {
+        let cells: Vec<String> = widths
+            .iter()
+            .enumerate()
+            .map(|(i, &width)| {
+                let cell = cells.get(i).map(String::as_str).unwrap_or_default();
+                pad(cell, width)
+            })
+            .collect();
+        cells.join("  ").trim_end().to_string()
+    };
+    let mut table = line(headers);
+    table.push('\n');
+    let rule: Vec<String> = widths.iter().map(|&w| "-".repeat(w)).collect();
+    table.push_str(&rule.join("  "));
+    table.push('\n');
+    for row in rows {
+        table.push_str(&line(row));
+        table.push('\n');
+    }
+    table
+}
+
+/// Cut a cell down to `max_width` terminal columns, marking the cut with an ellipsis.
+/// Line breaks are shown as spaces so each row stays on one line.
+pub fn truncate_cell(cell: &str, max_width: usize) -> String {
+    let cell = cell.replace(['\r', '\n'], " ");
+    if max_width == 0 || cell.width() <= max_width {
+        return cell;
+    }
+    let mut truncated = String::new();
+    let mut width = 0;
+    for c in cell.chars() {
+        let w = c.to_string().width();
+        if width + w + 1 > max_width {
+            break;
+        }
+        truncated.push(c);
+        width += w;
+    }
+    truncated.push(ELLIPSIS);
+    truncated
+}
+
+/// Render rows as a github flavored markdown table, numeric columns are right aligned.
+pub fn render_markdown(headers: &[String], rows: &[Vec<String>]) -> String {
+    let escape = |cell: &str| cell.replace('|', "\\|").replace(['\r', '\n'], "<br>");
+    let headers: Vec<String> = headers.iter().map(|h| escape(h)).collect();
+    let rows: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| row.iter().map(|c| escape(c)).collect())
+        .collect();
+    let numeric: Vec<bool> = (0..headers.len())
+        .map(|i| {
+            let mut cells = rows
+                .iter()
+                .filter_map(|r| r.get(i))
+                .filter(|c| !c.is_empty());
+            cells.clone().next().is_some() && cells.all(|c| c.parse::<f64>().is_ok())
+        })
+        .collect();
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.width().max(3)).collect();
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.width());
+        }
+    }
+    let line = |cells: &[String]| {
+        let cells: Vec<String> = widths
+            .iter()
+            .enumerate()
+            .map(|(i, &width)| {
+                let cell = cells.get(i).map(String::as_str).unwrap_or_default();
+                if numeric[i] {
+                    format!("{}{}", " ".repeat(width - cell.width()), cell)
+                } else {
+                    pad(cell, width)
+                }
+            })
+            .collect();
+        format!("| {} |\n", cells.join(" | "))
+    };
+    let mut table = line(&headers);
+    let rule: Vec<String> = widths
+        .iter()
+        .zip(&numeric)
+        .map(|(&w, &numeric)| match numeric {
+            true => format!("{}:", "-".repeat(w - 1)),
+            false => "-".repeat(w),
+        })
+        .collect();
+    table.push_str(&format!("| {} |\n", rule.join(" | ")));
+    for row in &rows {
+        table.push_str(&line(row));
+    }
+    table
+}
+
+/// Render rows as an html table, to be dropped into a page template.
+pub fn render_html(headers: &[String], rows: &[Vec<String>]) -> String {
+    let cells = |tag: &str, cells: &[String]| {
+        let cells: String = cells
+            .iter()
+            .map(|cell| format!("<{tag}>{}</{tag}>", escape_html(cell)))
+            .collect();
+        format!("<tr>{}</tr>\n", cells)
+    };
+    let mut table = String::from("<table>\n<thead>\n");
+    table.push_str(&cells("th", headers));
+    table.push_str("</thead>\n<tbody>\n");
+    for row in rows {
+        table.push_str(&cells("td", row));
+    }
+    table.push_str("</tbody>\n</table>");
+    table
+}
+
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Left align `cell` in `width` terminal columns.
+fn pad(cell: &str, width: usize) -> String {
+    format!("{}{}", cell, " ".repeat(width.saturating_sub(cell.width())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_table() {
+        let headers = vec!["Name".to_string(), "Kit".to_string()];
+        let rows = vec![
+            vec!["Buffon".to_string(), "77".to_string()],
+            vec!["Can".to_string(), "23".to_string()],
+        ];
+        assert_eq!(
+            render_table(&headers, &rows),
+            "Name    Kit\n------  ---\nBuffon  77\nCan     23\n"
+        );
+    }
+
+    #[test]
+    fn test_wide_cells_and_truncation() {
+        let headers = vec!["Name".to_string(), "Kit".to_string()];
+        let rows = vec![vec!["李娜".to_string(), "9".to_string()]];
+        assert_eq!(
+            render_table(&headers, &rows),
+            "Name  Kit\n----  ---\n李娜  9\n"
+        );
+        assert_eq!(truncate_cell("Gianluigi Buffon", 8), "Gianlui…");
+        assert_eq!(truncate_cell("李娜李娜", 5), "李娜…");
+        assert_eq!(truncate_cell("Buffon", 0), "Buffon");
+    }
+
+    #[test]
+    fn test_markdown_and_html() {
+        let headers = vec!["Name".to_string(), "Kit".to_string()];
+        let rows = vec![
+            vec!["Buffon | GK".to_string(), "77".to_string()],
+            vec!["<Can>".to_string(), "".to_string()],
+        ];
+        assert_eq!(
+            render_markdown(&headers, &rows),
+            "| Name         | Kit |\n| ------------ | --: |\n| Buffon \\| GK |  77 |\n| <Can>        |     |\n"
+        );
+        assert!(render_html(&headers, &rows).contains("<tr><td>&lt;Can&gt;</td><td></td></tr>"));
+    }
+}
@@ -104,7 +104,7 @@ impl KeyLoader for Ed25519Verify {
 
 impl KeyGenerator for Blake3 {
     fn generate() -> Result<Vec<Vec<u8>>> {
-        let key = gen_pass::process_genpass(32, true, true, true, true)?;
+        let key = gen_pass::process_genpass(32, &gen_pass::PasswordPolicy::default())?;
         Ok(vec![key.as_bytes().to_vec()])
     }
 }